@@ -0,0 +1,26 @@
+//! Compares request token issuance throughput for each entropy source.
+//!
+//! Run with `cargo bench --bench entropy`.
+use std::sync::Arc;
+use std::time::Instant;
+use xsrf::{CookieToken, Entropy, OtpPool, XsrfConfig};
+
+const TOKENS: usize = 500_000;
+
+fn bench(name: &str, entropy: Entropy) {
+    let config = XsrfConfig::new().with_entropy(entropy);
+    let ct = CookieToken::new();
+    let start = Instant::now();
+    for _ in 0..TOKENS {
+        std::hint::black_box(ct.gen_req_token_with(&config));
+    }
+    let ops = TOKENS as f64 / start.elapsed().as_secs_f64();
+    println!("{:<14} {:>12.0} tokens/s", name, ops);
+}
+
+fn main() {
+    bench("os", Entropy::Os);
+    bench("thread-local", Entropy::ThreadLocal);
+    bench("reseed-1024", Entropy::Reseeding(1024));
+    bench("pool", Entropy::Pool(Arc::new(OtpPool::new(4096))));
+}
@@ -0,0 +1,57 @@
+//! Compares replay store throughput under contention.
+//!
+//! Run with `cargo bench --bench replay`.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use xsrf::{BloomReplayStore, MemoryReplayStore, ReplayStore, ShardedReplayStore};
+
+const PER_THREAD: usize = 200_000;
+
+fn bench(name: &str, store: Arc<dyn ReplayStore>, threads: usize) {
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                for i in 0..PER_THREAD {
+                    let mut nonce = [0; 32];
+                    nonce[..8].copy_from_slice(&((t * PER_THREAD + i) as u64).to_le_bytes());
+                    nonce[8..16].copy_from_slice(
+                        &(i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15).to_le_bytes(),
+                    );
+                    std::hint::black_box(store.insert(nonce, 0));
+                }
+            })
+        })
+        .collect();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    let elapsed = start.elapsed();
+    let ops = (threads * PER_THREAD) as f64 / elapsed.as_secs_f64();
+    println!(
+        "{:<10} threads={:<3} {:>12.0} inserts/s",
+        name, threads, ops
+    );
+}
+
+fn main() {
+    let retention = Duration::from_secs(3600);
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for &threads in &[1, cores] {
+        bench(
+            "memory",
+            Arc::new(MemoryReplayStore::new(retention)),
+            threads,
+        );
+        bench(
+            "sharded",
+            Arc::new(ShardedReplayStore::new(retention)),
+            threads,
+        );
+        let expected = threads * PER_THREAD;
+        bench(
+            "bloom",
+            Arc::new(BloomReplayStore::new(expected, 1e-9, retention)),
+            threads,
+        );
+    }
+}
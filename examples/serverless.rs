@@ -0,0 +1,68 @@
+//! The keyed, stateless setup for serverless functions such as AWS Lambda.
+//!
+//! Every instance loads the same master secret from the environment, which
+//! with Lambda may be decrypted from KMS at deploy time, and derives its
+//! token key from it. Nothing else is shared between instances.
+//!
+//! `handle` stands in for the function handler, which with `lambda_http`
+//! would be passed to `lambda_http::run`. Run with
+//! `XSRF_SECRET=<base64> cargo run --example serverless`.
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+use xsrf::{
+    CookieToken, CsrfProtection, Key, RequestToken, Secret, SignedDoubleSubmit, XsrfConfig,
+};
+
+/// State initialized once per cold start, and reused by warm invocations.
+struct Instance {
+    key: Key,
+    config: XsrfConfig,
+}
+
+fn instance() -> &'static Instance {
+    static INSTANCE: OnceLock<Instance> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        // fail the cold start, rather than serve tokens from a broken
+        // entropy source.
+        let config = XsrfConfig::stateless();
+        xsrf::self_test(&config).expect("xsrf self test failed");
+        let secret = Secret::from_env("XSRF_SECRET").unwrap_or_else(|err| {
+            eprintln!("{}, using a random secret for this demo", err);
+            Secret::new(CookieToken::new().as_bytes()).unwrap()
+        });
+        Instance {
+            key: secret.token_key(),
+            config,
+        }
+    })
+}
+
+fn protection(session_id: &str) -> SignedDoubleSubmit {
+    let instance = instance();
+    SignedDoubleSubmit::for_session(&instance.key, session_id.as_bytes())
+        .with_config(instance.config.clone())
+}
+
+/// Handle a request for `session_id`, verifying `submitted` if present and
+/// returning the status and a fresh request token for the response.
+fn handle(session_id: &str, cookie: &CookieToken, submitted: Option<&str>) -> (u16, String) {
+    let protection = protection(session_id);
+    if let Some(token) = submitted {
+        let verified = RequestToken::try_from(token).and_then(|rt| protection.verify(cookie, rt));
+        if let Err(err) = verified {
+            return (403, err.to_string());
+        }
+    }
+    (200, protection.issue_req_token(cookie).to_string())
+}
+
+fn main() {
+    let cookie = CookieToken::new();
+    let (_, token) = handle("session-1", &cookie, None);
+    println!("issued: {}", token);
+    println!("submit: {:?}", handle("session-1", &cookie, Some(&token)));
+    println!(
+        "other session: {:?}",
+        handle("session-2", &cookie, Some(&token))
+    );
+}
@@ -0,0 +1,89 @@
+//! Signed links for out-of-band flows, such as email confirmation.
+//!
+//! A link to unsubscribe or confirm an account is followed from an email,
+//! without any cookie, yet faces the same forgery concerns as a form. An
+//! [`ActionLinks`] derives a `CookieToken` from `HMAC(key, subject)`, where
+//! the subject identifies who the link is for, and issues request tokens
+//! from it with the action as their purpose. Links therefore only work for
+//! the subject and action they were signed for, and expire with the TTL.
+use crate::sha256::hmac;
+use crate::{CookieToken, Key, RequestToken, Result, XsrfConfig};
+use std::time::Duration;
+
+const DOMAIN: &[u8] = b"xsrf action link\0";
+
+/// Signs and verifies tokens for links sent out-of-band.
+#[derive(Clone, Debug)]
+pub struct ActionLinks {
+    key: Key,
+    config: XsrfConfig,
+}
+
+impl ActionLinks {
+    /// Sign links with `key`, valid for `ttl`.
+    pub fn new(key: &Key, ttl: Duration) -> ActionLinks {
+        Self {
+            key: key.clone(),
+            config: XsrfConfig::new().with_ttl(ttl),
+        }
+    }
+
+    /// Sign and verify links with the given config instead, such as one with
+    /// a replay store to make links single use. The config should have a
+    /// TTL, or links never expire.
+    pub fn with_config(mut self, config: XsrfConfig) -> ActionLinks {
+        self.config = config;
+        self
+    }
+
+    fn bind(&self, subject: &[u8]) -> CookieToken {
+        let len = (subject.len() as u64).to_be_bytes();
+        CookieToken::from_bytes(hmac(self.key.as_bytes(), &[DOMAIN, &len, subject]))
+    }
+
+    /// Sign a token for `action`, such as `"unsubscribe"`, on behalf of
+    /// `subject`, such as the user id.
+    ///
+    /// Panics if `action` is longer than 255 bytes.
+    pub fn sign(&self, action: &str, subject: &[u8]) -> RequestToken {
+        self.bind(subject)
+            .gen_req_token_purpose_with(action, &self.config)
+    }
+
+    /// Verify a token was signed for `action` on behalf of `subject`, and
+    /// has not expired.
+    pub fn verify(&self, action: &str, subject: &[u8], token: RequestToken) -> Result<()> {
+        self.bind(subject)
+            .verify_req_token_purpose_with(token, action, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActionLinks;
+    use crate::{Error, Key, TestClock, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn action_links() {
+        let clock = TestClock::new(1_000_000);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let config = XsrfConfig::new().with_clock(clock.clone()).with_ttl(day);
+        let links = ActionLinks::new(&Key::generate(), day).with_config(config);
+        let rt = links.sign("unsubscribe", b"user-1");
+        links.verify("unsubscribe", b"user-1", rt.clone()).unwrap();
+        assert_eq!(
+            links.verify("unsubscribe", b"user-2", rt.clone()),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(
+            links.verify("delete-account", b"user-1", rt.clone()),
+            Err(Error::WrongPurpose)
+        );
+        clock.advance(day + Duration::from_secs(1));
+        assert_eq!(
+            links.verify("unsubscribe", b"user-1", rt),
+            Err(Error::Expired)
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Verifying against several cookie tokens at once.
+//!
+//! A session layer may legitimately hold several valid `CookieToken`s, such
+//! as one per device of a user. Every candidate is checked, whether or not
+//! an earlier one matched, so the time taken does not reveal which did.
+use crate::{CookieToken, Error, RequestToken, Result, XsrfConfig, TOKEN_LEN};
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+
+/// The index of the cookie token `token` is derived from, checking all of
+/// them.
+fn find(cookies: &[CookieToken], token: &RequestToken) -> Option<usize> {
+    let mut expected = [0; TOKEN_LEN];
+    crate::xor_into(&token.otp, &token.mask, &mut expected);
+    let mut found = subtle::Choice::from(0);
+    let mut index = 0u64;
+    for (i, cookie) in cookies.iter().enumerate() {
+        let eq = expected.ct_eq(&cookie.derive(&token.claims));
+        index.conditional_assign(&(i as u64), eq & !found);
+        found |= eq;
+    }
+    bool::from(found).then_some(index as usize)
+}
+
+/// Verify the token is derived from any of `cookies`. Any claims it carries
+/// are bound, but not enforced.
+pub fn verify_req_token_any(cookies: &[CookieToken], token: RequestToken) -> Result<()> {
+    find(cookies, &token)
+        .map(|_| ())
+        .ok_or(Error::TokenMismatch)
+}
+
+/// Verify the token is derived from any of `cookies`, and that it satisfies
+/// `config`.
+pub fn verify_req_token_any_with(
+    cookies: &[CookieToken],
+    token: RequestToken,
+    config: &XsrfConfig,
+) -> Result<()> {
+    match find(cookies, &token) {
+        Some(i) => config.verify(&cookies[i], &token, None),
+        None => Err(Error::TokenMismatch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_req_token_any, verify_req_token_any_with};
+    use crate::{CookieToken, Error, MemoryReplayStore, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn any_of_several() {
+        let cookies = [CookieToken::new(), CookieToken::new(), CookieToken::new()];
+        for ct in cookies.iter() {
+            verify_req_token_any(&cookies, ct.gen_req_token()).unwrap();
+        }
+        assert_eq!(
+            verify_req_token_any(&cookies, CookieToken::new().gen_req_token()),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(
+            verify_req_token_any(&[], cookies[0].gen_req_token()),
+            Err(Error::TokenMismatch)
+        );
+
+        let config =
+            XsrfConfig::new().with_replay_store(MemoryReplayStore::new(Duration::from_secs(60)));
+        let rt = cookies[1].gen_req_token_with(&config);
+        verify_req_token_any_with(&cookies, rt.clone(), &config).unwrap();
+        assert_eq!(
+            verify_req_token_any_with(&cookies, rt, &config),
+            Err(Error::Replayed)
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! An audit trail of token issuance and verification.
+//!
+//! Configuring an [`AuditSink`] on an `XsrfConfig` records an
+//! [`AuditEvent`] whenever a token is issued or verified with it, so security
+//! teams can ship a complete trail to their SIEM. Events identify tokens by
+//! an [`AuditId`], a hash of their `TokenFamily`, so the families a
+//! `RevocationStore` is keyed by never appear in the trail.
+use crate::sha256::sha256;
+use crate::{Base64Url, TokenCodec, TokenFamily, VerifyOutcome};
+use std::fmt;
+use std::sync::Mutex;
+
+const DOMAIN: &[u8] = b"xsrf audit id\0";
+
+/// Length of the raw bytes of an `AuditId`.
+pub const AUDIT_ID_BYTES: usize = 16;
+
+/// Identifies a `TokenFamily` in audit events without revealing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AuditId {
+    data: [u8; AUDIT_ID_BYTES],
+}
+
+impl AuditId {
+    /// The raw bytes of the id.
+    pub const fn as_bytes(&self) -> &[u8; AUDIT_ID_BYTES] {
+        &self.data
+    }
+}
+
+impl fmt::Display for AuditId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::with_capacity(Base64Url.encoded_len(AUDIT_ID_BYTES));
+        Base64Url.encode(&self.data, &mut s);
+        f.write_str(&s)
+    }
+}
+
+impl TokenFamily {
+    /// The id audit events use for this family, to match them against a
+    /// session or user that knows its family.
+    pub fn audit_id(&self) -> AuditId {
+        let digest = sha256(&[DOMAIN, self.as_bytes()].concat());
+        let mut data = [0; AUDIT_ID_BYTES];
+        data.copy_from_slice(&digest[..AUDIT_ID_BYTES]);
+        AuditId { data }
+    }
+}
+
+/// Something that happened to a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AuditEvent {
+    TokenIssued {
+        family: AuditId,
+    },
+    TokenVerified {
+        family: AuditId,
+    },
+    TokenRejected {
+        family: AuditId,
+        reason: VerifyOutcome,
+    },
+    /// A `CookieToken` was replaced, such as on login.
+    KeyRotated {
+        from: AuditId,
+        to: AuditId,
+    },
+    TokenRevoked {
+        family: AuditId,
+    },
+}
+
+/// Receives audit events. It is called inline, so slow sinks should queue
+/// events rather than ship them synchronously.
+pub trait AuditSink: fmt::Debug + Send + Sync {
+    fn record(&self, event: AuditEvent);
+}
+
+/// An audit sink keeping events in memory, which is mostly useful in tests.
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl MemoryAuditSink {
+    pub fn new() -> MemoryAuditSink {
+        Self::default()
+    }
+
+    /// The events recorded so far, oldest first.
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl AuditSink for MemoryAuditSink {
+    fn record(&self, event: AuditEvent) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditEvent, MemoryAuditSink};
+    use crate::{CookieToken, MemoryRevocationStore, TokenFamily, VerifyOutcome, XsrfConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn audit_trail() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let config = XsrfConfig::new().with_audit_sink(sink.clone());
+        let ct = CookieToken::new();
+        let family = ct.family().audit_id();
+        assert_ne!(family.as_bytes(), ct.family().as_bytes());
+        let rt = ct.gen_req_token_with(&config);
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        let _ = CookieToken::new().verify_req_token_with(rt, &config);
+        let migration = ct.migrate(&TokenFamily::new(), Duration::from_secs(60), &config);
+        let to_family = migration.cookie_token().family();
+        let to = to_family.audit_id();
+        let store = MemoryRevocationStore::new().with_audit_sink(sink.clone());
+        store.revoke(to_family);
+
+        let events = sink.events();
+        assert_eq!(events[0], AuditEvent::TokenIssued { family });
+        assert_eq!(events[1], AuditEvent::TokenVerified { family });
+        assert!(matches!(
+            events[2],
+            AuditEvent::TokenRejected {
+                reason: VerifyOutcome::Mismatch,
+                ..
+            }
+        ));
+        assert_eq!(events[3], AuditEvent::KeyRotated { from: family, to });
+        assert_eq!(events[4], AuditEvent::TokenRevoked { family: to });
+    }
+}
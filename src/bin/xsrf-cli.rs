@@ -0,0 +1,68 @@
+//! Command line tool for generating, verifying and inspecting tokens.
+//!
+//! Run with `cargo run --features cli --bin xsrf-cli -- help`.
+use std::convert::TryFrom;
+use std::process::exit;
+use xsrf::{CookieToken, RequestToken};
+
+const USAGE: &str = "usage:
+  xsrf-cli cookie                     generate a cookie token
+  xsrf-cli request <cookie>           derive a request token from a cookie token
+  xsrf-cli verify <cookie> <request>  verify a pair, exiting non-zero on failure
+  xsrf-cli inspect <request>          describe a request token without verifying it";
+
+fn cookie_token(value: &str) -> CookieToken {
+    CookieToken::try_from(value).unwrap_or_else(|err| fail(&err))
+}
+
+fn fail(err: &dyn std::fmt::Display) -> ! {
+    eprintln!("error: {}", err);
+    exit(1)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["cookie"] => println!("{}", CookieToken::new()),
+        ["request", cookie] => println!("{}", cookie_token(cookie).gen_req_token()),
+        ["verify", cookie, request] => {
+            let rt = RequestToken::try_from(*request).unwrap_or_else(|err| fail(&err));
+            match cookie_token(cookie).verify_req_token(rt) {
+                Ok(()) => println!("ok"),
+                Err(err) => fail(&format!("{} ({})", err, err.code())),
+            }
+        }
+        ["inspect", request] => {
+            let info = RequestToken::inspect(request);
+            println!("len: {}", info.len);
+            let or_none = |v: Option<String>| v.unwrap_or_else(|| "-".to_owned());
+            println!("version: {}", or_none(info.version.map(|v| v.to_string())));
+            println!(
+                "encoding: {}",
+                or_none(info.encoding.map(|e| format!("{:?}", e)))
+            );
+            println!(
+                "issued_at: {}",
+                or_none(info.issued_at.map(|v| v.to_string()))
+            );
+            println!("origin: {}", or_none(info.origin.map(|v| v.to_string())));
+            println!("purpose: {}", or_none(info.purpose));
+            println!("scope: {}", or_none(info.scope));
+            println!("audience: {}", or_none(info.audience));
+            println!(
+                "max_uses: {}",
+                or_none(info.max_uses.map(|v| v.to_string()))
+            );
+            println!("environment: {}", or_none(info.environment));
+            if let Some(problem) = info.problem {
+                fail(&problem);
+            }
+        }
+        ["help"] | ["--help"] | ["-h"] => println!("{}", USAGE),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Binding request tokens to properties of the connection.
+//!
+//! A [`Binding`] collects values the client cannot change without the token
+//! becoming invalid, such as the TLS channel. Binding a `CookieToken` derives
+//! a new one from `HMAC(cookie_token, binding)`, and request tokens issued
+//! from the bound token only verify against a token bound to the same
+//! values. Stolen request tokens replayed from elsewhere then fail with
+//! `Error::TokenMismatch`.
+//!
+//! Binding can also cover the critical fields of a form, such as the amount
+//! and recipient of a transfer, so a stolen token can't submit other values.
+use crate::extract::form::sole_form_field;
+use crate::sha256::{hmac, sha256, Sha256};
+use crate::{CookieToken, CsrfProtection, Request, RequestToken, Result};
+use std::net::IpAddr;
+
+const DOMAIN: &[u8] = b"xsrf binding\0";
+
+const TAG_CHANNEL: u8 = 1;
+const TAG_CLIENT: u8 = 2;
+const TAG_IP: u8 = 3;
+const TAG_FIELDS: u8 = 4;
+
+/// The headers hashed by [`client_fingerprint`].
+const FINGERPRINT_HEADERS: [&str; 2] = ["user-agent", "accept-language"];
+
+/// The values request tokens are bound to. Nothing is bound by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Binding {
+    channel: Option<Vec<u8>>,
+    client: Option<Vec<u8>>,
+    ip: Option<Vec<u8>>,
+    fields: Option<Vec<u8>>,
+}
+
+impl Binding {
+    pub fn new() -> Binding {
+        Self::default()
+    }
+
+    /// Bind to the TLS channel, given its channel binding value such as the
+    /// `tls-exporter` keying material. Only useful where TLS terminates in
+    /// the same process, since a proxy has a different channel.
+    pub fn with_channel(mut self, channel_binding: &[u8]) -> Binding {
+        self.channel = Some(channel_binding.to_vec());
+        self
+    }
+
+    /// Bind to a fingerprint of the client, such as the one computed by
+    /// [`client_fingerprint`]. Legitimate clients change their fingerprint on
+    /// upgrades, invalidating their tokens, so this suits short lived pages.
+    pub fn with_client(mut self, fingerprint: &[u8]) -> Binding {
+        self.client = Some(sha256(fingerprint).to_vec());
+        self
+    }
+
+    /// Bind to the client IP, within `tolerance`. Clients roaming out of
+    /// the tolerated network need a new token, which admin panels and the
+    /// like can usually live with.
+    pub fn with_ip(mut self, ip: IpAddr, tolerance: IpTolerance) -> Binding {
+        let mut out = Vec::new();
+        match ip {
+            IpAddr::V4(v4) => {
+                out.push(tolerance.v4);
+                out.extend_from_slice(&mask(&v4.octets(), tolerance.v4));
+            }
+            IpAddr::V6(v6) => {
+                out.push(tolerance.v6);
+                out.extend_from_slice(&mask(&v6.octets(), tolerance.v6));
+            }
+        }
+        self.ip = Some(out);
+        self
+    }
+
+    /// Bind to the values of critical form fields, supplied when issuing
+    /// the token and again from the submitted form when verifying it. The
+    /// order of the fields does not matter, but a field missing from either
+    /// side does.
+    pub fn with_fields(mut self, fields: &[(&str, &str)]) -> Binding {
+        let mut fields = fields.to_vec();
+        fields.sort_unstable();
+        let mut h = Sha256::new();
+        for (name, value) in fields.iter() {
+            for part in [name, value].iter() {
+                h.update(&(part.len() as u64).to_be_bytes());
+                h.update(part.as_bytes());
+            }
+        }
+        self.fields = Some(h.finish().to_vec());
+        self
+    }
+
+    /// Bind to the values of the `names` fields in a urlencoded `body`,
+    /// like `with_fields`.
+    ///
+    /// Fails with `Error::InvalidToken` if one of the fields is repeated or
+    /// malformed, or the body is, since the app's own form parser might then
+    /// see a different value than the one bound, such as the last one.
+    pub fn with_form_fields(self, body: &[u8], names: &[&str]) -> Result<Binding> {
+        let mut values = Vec::with_capacity(names.len());
+        for &name in names {
+            if let Some(v) = sole_form_field(body, name)? {
+                values.push((name, v));
+            }
+        }
+        let fields: Vec<(&str, &str)> = values.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        Ok(self.with_fields(&fields))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let fields = [
+            (TAG_CHANNEL, &self.channel),
+            (TAG_CLIENT, &self.client),
+            (TAG_IP, &self.ip),
+            (TAG_FIELDS, &self.fields),
+        ];
+        for (tag, value) in fields.iter() {
+            if let Some(v) = value {
+                out.push(*tag);
+                out.extend_from_slice(&(v.len() as u64).to_be_bytes());
+                out.extend_from_slice(v);
+            }
+        }
+        out
+    }
+}
+
+/// How much of the client IP a [`Binding`] covers, as prefix lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpTolerance {
+    v4: u8,
+    v6: u8,
+}
+
+impl IpTolerance {
+    /// The exact address.
+    pub const EXACT: IpTolerance = IpTolerance { v4: 32, v6: 128 };
+
+    /// The /24 for IPv4 and the /64 for IPv6, which covers most NAT pools
+    /// and IPv6 privacy addresses.
+    pub const NETWORK: IpTolerance = IpTolerance { v4: 24, v6: 64 };
+
+    /// Custom prefix lengths, which must not exceed 32 and 128.
+    pub fn new(v4: u8, v6: u8) -> IpTolerance {
+        assert!(v4 <= 32 && v6 <= 128, "invalid prefix length");
+        Self { v4, v6 }
+    }
+}
+
+/// Keep only the first `prefix` bits of `octets`.
+fn mask<const N: usize>(octets: &[u8; N], prefix: u8) -> [u8; N] {
+    let mut out = *octets;
+    for (i, b) in out.iter_mut().enumerate() {
+        let keep = (prefix as usize).saturating_sub(i * 8).min(8);
+        *b &= !(0xffu8.checked_shr(keep as u32).unwrap_or(0));
+    }
+    out
+}
+
+/// A fingerprint of the client profile, from the `User-Agent` and
+/// `Accept-Language` headers.
+pub fn client_fingerprint<R: Request + ?Sized>(req: &R) -> [u8; 32] {
+    let mut h = Sha256::new();
+    for name in FINGERPRINT_HEADERS.iter() {
+        let value = req.header(name).unwrap_or("");
+        h.update(&(value.len() as u64).to_be_bytes());
+        h.update(value.as_bytes());
+    }
+    h.finish()
+}
+
+impl CookieToken {
+    /// The `CookieToken` request tokens bound to `binding` are derived from.
+    pub fn bind(&self, binding: &Binding) -> CookieToken {
+        self.derive_child(hmac(&self.data, &[DOMAIN, &binding.encode()]))
+    }
+}
+
+/// A strategy whose request tokens are bound to `binding`.
+///
+/// Like the binding itself, this is constructed per request.
+#[derive(Clone, Debug)]
+pub struct Bound<P> {
+    inner: P,
+    binding: Binding,
+}
+
+impl<P: CsrfProtection> Bound<P> {
+    pub fn new(inner: P, binding: Binding) -> Bound<P> {
+        Self { inner, binding }
+    }
+}
+
+impl<P: CsrfProtection> CsrfProtection for Bound<P> {
+    fn issue_cookie_token(&self) -> CookieToken {
+        self.inner.issue_cookie_token()
+    }
+
+    fn issue_req_token(&self, cookie: &CookieToken) -> RequestToken {
+        self.inner.issue_req_token(&cookie.bind(&self.binding))
+    }
+
+    fn verify(&self, cookie: &CookieToken, token: RequestToken) -> Result<()> {
+        self.inner.verify(&cookie.bind(&self.binding), token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_fingerprint, Binding, Bound, IpTolerance};
+    use crate::request::tests::TestRequest;
+    use crate::{
+        CookieToken, CsrfProtection, Error, Key, MemoryRevocationStore, Plain, SignedDoubleSubmit,
+        TokenFamily, XsrfConfig,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn bound_to_channel() {
+        let ct = CookieToken::new();
+        let a = Bound::new(Plain, Binding::new().with_channel(b"channel-a"));
+        let b = Bound::new(Plain, Binding::new().with_channel(b"channel-b"));
+        let rt = a.issue_req_token(&ct);
+        a.verify(&ct, rt.clone()).unwrap();
+        assert_eq!(b.verify(&ct, rt.clone()), Err(Error::TokenMismatch));
+        assert_eq!(ct.verify_req_token(rt), Err(Error::TokenMismatch));
+    }
+
+    #[test]
+    fn bound_to_client() {
+        let ct = CookieToken::new();
+        let firefox = TestRequest::new("POST", "/").header("User-Agent", "Firefox");
+        let curl = TestRequest::new("POST", "/").header("User-Agent", "curl");
+        let bound = |req| Bound::new(Plain, Binding::new().with_client(&client_fingerprint(req)));
+        let rt = bound(&firefox).issue_req_token(&ct);
+        bound(&firefox).verify(&ct, rt.clone()).unwrap();
+        assert_eq!(bound(&curl).verify(&ct, rt), Err(Error::TokenMismatch));
+    }
+
+    #[test]
+    fn bound_tokens_stay_revocable() {
+        let store = Arc::new(MemoryRevocationStore::new());
+        let config = XsrfConfig::new().with_revocation_store(store.clone());
+        let family = TokenFamily::new();
+        let ct = CookieToken::new_in_family(&family);
+        let p = Bound::new(
+            SignedDoubleSubmit::for_session(&Key::generate(), b"session").with_config(config),
+            Binding::new().with_channel(b"channel"),
+        );
+        let rt = p.issue_req_token(&ct);
+        p.verify(&ct, rt.clone()).unwrap();
+        store.revoke(family);
+        assert_eq!(p.verify(&ct, rt), Err(Error::Revoked));
+    }
+
+    #[test]
+    fn bound_to_form_fields() {
+        let ct = CookieToken::new();
+        let critical = ["amount", "to"];
+        let issued = Binding::new().with_fields(&[("to", "alice"), ("amount", "10")]);
+        let rt = Bound::new(Plain, issued).issue_req_token(&ct);
+        let submitted = |body: &str| {
+            let binding = Binding::new().with_form_fields(body.as_bytes(), &critical)?;
+            Bound::new(Plain, binding).verify(&ct, rt.clone())
+        };
+        submitted("amount=10&note=hi&to=alice").unwrap();
+        let err = Err(Error::TokenMismatch);
+        assert_eq!(submitted("amount=1000&to=alice"), err);
+        assert_eq!(submitted("amount=10"), err);
+        // parsers disagree on which of these the handler would see.
+        let err = Err(Error::InvalidToken);
+        assert_eq!(submitted("amount=10&to=alice&to=mallory"), err);
+        assert_eq!(submitted("amount=10&to=alice&to=mallory%zz"), err);
+    }
+
+    #[test]
+    fn bound_to_ip_prefix() {
+        let ct = CookieToken::new();
+        let bound = |ip: &str, t| Bound::new(Plain, Binding::new().with_ip(ip.parse().unwrap(), t));
+        let rt = bound("192.0.2.10", IpTolerance::NETWORK).issue_req_token(&ct);
+        bound("192.0.2.99", IpTolerance::NETWORK)
+            .verify(&ct, rt.clone())
+            .unwrap();
+        let err = Err(Error::TokenMismatch);
+        assert_eq!(
+            bound("192.0.3.10", IpTolerance::NETWORK).verify(&ct, rt.clone()),
+            err
+        );
+        assert_eq!(bound("192.0.2.10", IpTolerance::EXACT).verify(&ct, rt), err);
+
+        let rt = bound("2001:db8::1", IpTolerance::NETWORK).issue_req_token(&ct);
+        bound("2001:db8::ffff:1", IpTolerance::NETWORK)
+            .verify(&ct, rt.clone())
+            .unwrap();
+        assert_eq!(
+            bound("2001:db8:0:1::1", IpTolerance::NETWORK).verify(&ct, rt),
+            err
+        );
+    }
+}
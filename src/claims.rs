@@ -0,0 +1,188 @@
+//! Claims optionally embedded in a `RequestToken`.
+//!
+//! Tokens without claims use the original format, where the mask is simply
+//! the xor of the one-time-pad and the `CookieToken`. Tokens with claims
+//! instead use an HMAC of the claims keyed by the `CookieToken` in place of
+//! the `CookieToken` itself, which binds the claims to it. The claims are not
+//! secret, and are encoded after the fixed part of the token:
+//!
+//! ```text
+//! <otp><mask>.<claims>
+//! ```
+//!
+//! The binary form of the claims is a version byte followed by fields in
+//! ascending tag order, each being a tag byte, a length byte and the value.
+use crate::{Error, Result};
+
+/// The version byte of the claims encoding.
+pub(crate) const VERSION: u8 = 2;
+
+/// Separates the fixed part of a token from its claims.
+pub(crate) const SEPARATOR: char = '.';
+
+const TAG_ISSUED_AT: u8 = 1;
+const TAG_ORIGIN: u8 = 2;
+const TAG_PURPOSE: u8 = 3;
+const TAG_SCOPE: u8 = 4;
+const TAG_AUDIENCE: u8 = 5;
+const TAG_MAX_USES: u8 = 6;
+const TAG_ENVIRONMENT: u8 = 7;
+
+/// The maximum length of a single field value.
+pub(crate) const MAX_FIELD_LEN: usize = u8::MAX as usize;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Claims {
+    /// Unix time in seconds at which the token was issued.
+    pub(crate) issued_at: Option<u64>,
+    /// Unix time in seconds at which the first token in a chain of renewals
+    /// was issued, if different from `issued_at`.
+    pub(crate) origin: Option<u64>,
+    /// The purpose the token was issued for, such as a specific form.
+    pub(crate) purpose: Option<String>,
+    /// The cookie scope the token was issued under: empty for host-only, or
+    /// the cookie domain.
+    pub(crate) scope: Option<String>,
+    /// The audience the token was minted for, such as another domain.
+    pub(crate) audience: Option<String>,
+    /// How many times the token may be used, if more than once.
+    pub(crate) max_uses: Option<u8>,
+    /// The deployment environment the token was issued in, such as staging.
+    pub(crate) environment: Option<String>,
+}
+
+impl Claims {
+    pub(crate) const EMPTY: Claims = Claims {
+        issued_at: None,
+        origin: None,
+        purpose: None,
+        scope: None,
+        audience: None,
+        max_uses: None,
+        environment: None,
+    };
+
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        if let Some(v) = self.issued_at {
+            push_field(&mut out, TAG_ISSUED_AT, &v.to_be_bytes());
+        }
+        if let Some(v) = self.origin {
+            push_field(&mut out, TAG_ORIGIN, &v.to_be_bytes());
+        }
+        if let Some(v) = &self.purpose {
+            push_field(&mut out, TAG_PURPOSE, v.as_bytes());
+        }
+        if let Some(v) = &self.scope {
+            push_field(&mut out, TAG_SCOPE, v.as_bytes());
+        }
+        if let Some(v) = &self.audience {
+            push_field(&mut out, TAG_AUDIENCE, v.as_bytes());
+        }
+        if let Some(v) = self.max_uses {
+            push_field(&mut out, TAG_MAX_USES, &[v]);
+        }
+        if let Some(v) = &self.environment {
+            push_field(&mut out, TAG_ENVIRONMENT, v.as_bytes());
+        }
+        out
+    }
+
+    pub(crate) fn decode(mut data: &[u8]) -> Result<Claims> {
+        if data.first() != Some(&VERSION) {
+            return Err(Error::InvalidToken);
+        }
+        data = &data[1..];
+        let mut claims = Claims::default();
+        let mut last_tag = 0;
+        while !data.is_empty() {
+            if data.len() < 2 {
+                return Err(Error::InvalidToken);
+            }
+            let (tag, len) = (data[0], data[1] as usize);
+            if tag <= last_tag || data.len() < 2 + len {
+                return Err(Error::InvalidToken);
+            }
+            let value = &data[2..2 + len];
+            match tag {
+                TAG_ISSUED_AT => claims.issued_at = Some(decode_u64(value)?),
+                TAG_ORIGIN => claims.origin = Some(decode_u64(value)?),
+                TAG_PURPOSE => claims.purpose = Some(decode_str(value)?),
+                TAG_SCOPE => claims.scope = Some(decode_str(value)?),
+                TAG_AUDIENCE => claims.audience = Some(decode_str(value)?),
+                TAG_MAX_USES => match value {
+                    [v] if *v > 1 => claims.max_uses = Some(*v),
+                    _ => return Err(Error::InvalidToken),
+                },
+                TAG_ENVIRONMENT => claims.environment = Some(decode_str(value)?),
+                _ => return Err(Error::InvalidToken),
+            }
+            last_tag = tag;
+            data = &data[2 + len..];
+        }
+        if claims.is_empty() {
+            return Err(Error::InvalidToken);
+        }
+        Ok(claims)
+    }
+}
+
+fn push_field(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    assert!(value.len() <= MAX_FIELD_LEN, "xsrf claim too long");
+    out.push(tag);
+    out.push(value.len() as u8);
+    out.extend_from_slice(value);
+}
+
+fn decode_str(value: &[u8]) -> Result<String> {
+    let v = std::str::from_utf8(value).map_err(|_| Error::InvalidToken)?;
+    Ok(v.to_owned())
+}
+
+fn decode_u64(value: &[u8]) -> Result<u64> {
+    let mut b = [0; 8];
+    if value.len() != b.len() {
+        return Err(Error::InvalidToken);
+    }
+    b.copy_from_slice(value);
+    Ok(u64::from_be_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Claims;
+
+    #[test]
+    fn claims_round_trip() {
+        let claims = Claims {
+            issued_at: Some(1_600_000_000),
+            origin: Some(1_500_000_000),
+            purpose: Some("password-change".to_owned()),
+            scope: Some(String::new()),
+            audience: Some("admin.example.org".to_owned()),
+            max_uses: Some(5),
+            environment: Some("staging".to_owned()),
+        };
+        assert_eq!(Claims::decode(&claims.encode()).unwrap(), claims);
+    }
+
+    #[test]
+    fn claims_decode_is_strict() {
+        assert!(Claims::decode(&[]).is_err());
+        assert!(Claims::decode(&[2]).is_err());
+        assert!(Claims::decode(&[2, 1, 1, 0]).is_err());
+        assert!(Claims::decode(&[2, 9, 0]).is_err());
+        let mut dup = Claims {
+            issued_at: Some(1),
+            ..Claims::default()
+        }
+        .encode();
+        let fields = dup[1..].to_owned();
+        dup.extend_from_slice(&fields);
+        assert!(Claims::decode(&dup).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Time sources for time based features.
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of the current time, in seconds since the unix epoch.
+///
+/// All expiry logic goes through a `Clock`, which allows tests to control
+/// time using a `TestClock`.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// The system clock. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// A manually controlled clock. Clones share the same time, so a clone can
+/// be given to an `XsrfConfig` while the original is used to move time.
+#[derive(Clone, Debug, Default)]
+pub struct TestClock {
+    now: Arc<AtomicU64>,
+}
+
+impl TestClock {
+    pub fn new(now: u64) -> TestClock {
+        Self {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.fetch_add(by.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, TestClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_clock_clones_share_time() {
+        let clock = TestClock::new(100);
+        let other = clock.clone();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(other.now(), 105);
+        other.set(10);
+        assert_eq!(clock.now(), 10);
+    }
+}
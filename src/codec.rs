@@ -0,0 +1,169 @@
+//! Textual encodings for tokens.
+//!
+//! Tokens are fixed size byte arrays, and need to be turned into strings to be
+//! placed in cookies, forms and headers. The [`TokenCodec`] trait abstracts
+//! this, with [`Base64Url`] being the default used by the `Display` and
+//! `TryFrom<&str>` implementations.
+use crate::{CookieToken, Error, RequestToken, Result};
+
+/// An encoding for token bytes.
+///
+/// Implementations must never produce a `.`, which is used to separate the
+/// parts of tokens carrying claims.
+///
+/// Implementations must be strict: `decode` must reject any input that is not
+/// exactly `encoded_len(out.len())` long, or that does not decode to exactly
+/// `out.len()` bytes. Tokens are security sensitive, and being lenient here
+/// only serves to make them harder to reason about.
+pub trait TokenCodec {
+    /// The length of the encoded form of `len` bytes.
+    fn encoded_len(&self, len: usize) -> usize;
+
+    /// Append the encoded form of `data` to `out`.
+    fn encode(&self, data: &[u8], out: &mut String);
+
+    /// Decode `input` into `out`, which must be filled exactly.
+    fn decode(&self, input: &str, out: &mut [u8]) -> Result<()>;
+}
+
+/// Decode input of unknown decoded length. Several lengths may encode to the
+/// same number of characters, for example with padding, but a strict codec
+/// will only accept one of them.
+pub(crate) fn decode_vec<C: TokenCodec + ?Sized>(codec: &C, input: &str) -> Result<Vec<u8>> {
+    for n in (0..=input.len()).filter(|&n| codec.encoded_len(n) == input.len()) {
+        let mut out = vec![0; n];
+        if codec.decode(input, &mut out).is_ok() {
+            return Ok(out);
+        }
+    }
+    Err(Error::InvalidToken)
+}
+
+/// URL safe base64 with padding. This is the default codec.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Base64Url;
+
+static BC: base64::Config = base64::URL_SAFE;
+
+impl TokenCodec for Base64Url {
+    fn encoded_len(&self, len: usize) -> usize {
+        len.div_ceil(3) * 4
+    }
+
+    fn encode(&self, data: &[u8], out: &mut String) {
+        base64::encode_config_buf(data, BC, out);
+    }
+
+    fn decode(&self, input: &str, out: &mut [u8]) -> Result<()> {
+        if input.len() != self.encoded_len(out.len()) {
+            return Err(Error::InvalidToken);
+        }
+        // decode into a buffer with room for the worst case, since unpadded
+        // input of the right length may decode to more bytes than expected.
+        let decoded = base64::decode_config(input, BC).map_err(|_| Error::InvalidToken)?;
+        if decoded.len() != out.len() {
+            return Err(Error::InvalidToken);
+        }
+        out.copy_from_slice(&decoded);
+        Ok(())
+    }
+}
+
+/// URL safe base64 without padding, as produced by clients that strip it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Base64UrlNoPad;
+
+impl TokenCodec for Base64UrlNoPad {
+    fn encoded_len(&self, len: usize) -> usize {
+        (len * 4).div_ceil(3)
+    }
+
+    fn encode(&self, data: &[u8], out: &mut String) {
+        base64::encode_config_buf(data, base64::URL_SAFE_NO_PAD, out);
+    }
+
+    fn decode(&self, input: &str, out: &mut [u8]) -> Result<()> {
+        if input.len() != self.encoded_len(out.len()) {
+            return Err(Error::InvalidToken);
+        }
+        let decoded = base64::decode_config(input, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidToken)?;
+        if decoded.len() != out.len() {
+            return Err(Error::InvalidToken);
+        }
+        out.copy_from_slice(&decoded);
+        Ok(())
+    }
+}
+
+impl CookieToken {
+    /// Decode a token in the default encoding, also accepting it with its
+    /// padding stripped. Length and alphabet are still strictly validated.
+    pub fn decode_padding_optional(value: &str) -> Result<CookieToken> {
+        Self::decode_with(value, &Base64Url).or_else(|_| Self::decode_with(value, &Base64UrlNoPad))
+    }
+}
+
+impl RequestToken {
+    /// Decode a token in the default encoding, also accepting it with its
+    /// padding stripped. Length and alphabet are still strictly validated.
+    pub fn decode_padding_optional(value: &str) -> Result<RequestToken> {
+        Self::decode_with(value, &Base64Url).or_else(|_| Self::decode_with(value, &Base64UrlNoPad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Base64Url, Base64UrlNoPad, TokenCodec};
+    use crate::{CookieToken, RequestToken};
+
+    #[test]
+    fn base64url_round_trip() {
+        let data = [7u8; 32];
+        let mut s = String::new();
+        Base64Url.encode(&data, &mut s);
+        assert_eq!(s.len(), Base64Url.encoded_len(data.len()));
+        let mut out = [0u8; 32];
+        Base64Url.decode(&s, &mut out).unwrap();
+        assert_eq!(data, out);
+    }
+
+    #[test]
+    fn base64url_rejects_overlong_without_padding() {
+        let mut out = [0u8; 32];
+        assert!(Base64Url.decode(&"A".repeat(44), &mut out).is_err());
+        assert!(Base64Url.decode(&"A".repeat(43), &mut out).is_err());
+    }
+
+    #[test]
+    fn base64url_no_pad_is_strict() {
+        let data = [7u8; 32];
+        let mut s = String::new();
+        Base64UrlNoPad.encode(&data, &mut s);
+        assert_eq!(s.len(), 43);
+        let mut out = [0u8; 32];
+        Base64UrlNoPad.decode(&s, &mut out).unwrap();
+        assert_eq!(data, out);
+        assert!(Base64UrlNoPad.decode(&format!("{}=", s), &mut out).is_err());
+        // trailing bits must be zero.
+        assert!(Base64UrlNoPad
+            .decode(&format!("{}d", &s[..42]), &mut out)
+            .is_err());
+    }
+
+    #[test]
+    fn padding_optional() {
+        let ct = CookieToken::new();
+        for rt in [ct.gen_req_token(), ct.gen_req_token_purpose("checkout")] {
+            let padded = rt.to_string();
+            let stripped = padded.replace('=', "");
+            for s in [&padded, &stripped] {
+                let rt = RequestToken::decode_padding_optional(s).unwrap();
+                ct.verify_req_token(rt).unwrap();
+            }
+        }
+        let stripped = ct.to_string().replace('=', "");
+        assert!(CookieToken::decode_padding_optional(&stripped).is_ok());
+        assert!(CookieToken::decode_padding_optional(&stripped[1..]).is_err());
+    }
+}
@@ -0,0 +1,583 @@
+//! Configuration for issuing and verifying request tokens with claims.
+use crate::claims::{self, Claims};
+use crate::{
+    sha256, AuditEvent, AuditSink, Clock, ConfigError, CookieScope, CookieToken, Entropy, Error,
+    Nonce, ReplayStore, RequestToken, Result, RevocationStore, SystemClock, VerifyOutcome,
+    TOKEN_LEN,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Controls the claims embedded in issued request tokens, and how they are
+/// enforced at verification time.
+///
+/// The default configuration issues tokens without claims, which are
+/// identical to those issued by `CookieToken::gen_req_token`.
+#[derive(Clone, Debug)]
+pub struct XsrfConfig {
+    ttl: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    leeway: Duration,
+    clock: Arc<dyn Clock>,
+    replay_store: Option<Arc<dyn ReplayStore>>,
+    scope: Option<String>,
+    max_uses: Option<u8>,
+    revocation_store: Option<Arc<dyn RevocationStore>>,
+    entropy: Entropy,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    environment: Option<String>,
+}
+
+impl Default for XsrfConfig {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            max_lifetime: None,
+            leeway: Duration::from_secs(0),
+            clock: Arc::new(SystemClock),
+            replay_store: None,
+            scope: None,
+            max_uses: None,
+            revocation_store: None,
+            entropy: Entropy::default(),
+            audit_sink: None,
+            environment: None,
+        }
+    }
+}
+
+impl XsrfConfig {
+    pub fn new() -> XsrfConfig {
+        Self::default()
+    }
+
+    /// A preset for serverless deployments, where instances come and go and
+    /// share no state. Tokens expire after an hour, tolerating a minute of
+    /// clock skew between instances, and no replay store is used. Pair it
+    /// with `SignedDoubleSubmit` and a `Secret` shared by every instance.
+    ///
+    /// Pads come straight from the operating system, so a frozen and resumed
+    /// instance never continues from a snapshotted generator state.
+    pub fn stateless() -> XsrfConfig {
+        Self::default()
+            .with_ttl(Duration::from_secs(60 * 60))
+            .with_leeway(Duration::from_secs(60))
+            .with_entropy(Entropy::Os)
+    }
+
+    /// Use the given clock instead of the system clock.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> XsrfConfig {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Request tokens expire this long after they were issued or renewed.
+    pub fn with_ttl(mut self, ttl: Duration) -> XsrfConfig {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Request tokens expire this long after the first token in a chain of
+    /// renewals was issued, regardless of how recently they were renewed.
+    pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> XsrfConfig {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    /// Tolerate this much clock skew between the nodes issuing and
+    /// verifying tokens. Tokens issued up to this far in the future are
+    /// accepted, and expiry is extended by the same amount.
+    pub fn with_leeway(mut self, leeway: Duration) -> XsrfConfig {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Make request tokens one-time, by recording their nonce in the given
+    /// store when they are verified.
+    pub fn with_replay_store<S: ReplayStore + 'static>(mut self, store: S) -> XsrfConfig {
+        self.replay_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Embed the scope of the token cookie in issued tokens, and reject
+    /// tokens issued under a different scope. Services sharing a parent
+    /// domain can then tell tokens issued for a domain-wide cookie, which
+    /// any subdomain could have set, from host-only ones.
+    ///
+    /// Panics if the scope's domain is longer than 255 bytes.
+    pub fn with_scope(mut self, scope: CookieScope) -> XsrfConfig {
+        let scope = scope.claim();
+        assert!(scope.len() <= claims::MAX_FIELD_LEN, "xsrf scope too long");
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Allow issued tokens to be used this many times, for flows that
+    /// legitimately resubmit such as multi-step wizards. The budget is
+    /// bound to the token, and counted by the replay store.
+    ///
+    /// Panics if `max_uses` is zero.
+    pub fn with_max_uses(mut self, max_uses: u8) -> XsrfConfig {
+        assert!(max_uses > 0, "xsrf max uses must be positive");
+        self.max_uses = Some(max_uses).filter(|&n| n > 1);
+        self
+    }
+
+    /// Reject tokens whose `TokenFamily` was revoked in the given store. The
+    /// store is shared, so families can be revoked while it is in use.
+    pub fn with_revocation_store<S: RevocationStore + 'static>(
+        mut self,
+        store: Arc<S>,
+    ) -> XsrfConfig {
+        self.revocation_store = Some(store);
+        self
+    }
+
+    /// Take the one-time-pads of issued tokens from the given source.
+    pub fn with_entropy(mut self, entropy: Entropy) -> XsrfConfig {
+        self.entropy = entropy;
+        self
+    }
+
+    /// Record an `AuditEvent` in the given sink for every token issued and
+    /// verified with this config.
+    pub fn with_audit_sink<S: AuditSink + 'static>(mut self, sink: Arc<S>) -> XsrfConfig {
+        self.audit_sink = Some(sink);
+        self
+    }
+
+    /// Embed the deployment environment, such as `staging` or `prod`, in
+    /// issued tokens, and reject tokens issued in a different one. Without
+    /// this, a staging cookie leaking into production through a shared
+    /// parent domain verifies there whenever the two share a key.
+    ///
+    /// Panics if `environment` is longer than 255 bytes.
+    pub fn with_environment(mut self, environment: &str) -> XsrfConfig {
+        assert!(
+            environment.len() <= claims::MAX_FIELD_LEN,
+            "xsrf environment too long"
+        );
+        self.environment = Some(environment.to_owned());
+        self
+    }
+
+    pub(crate) fn audit(&self, event: impl FnOnce() -> AuditEvent) {
+        if let Some(sink) = &self.audit_sink {
+            sink.record(event());
+        }
+    }
+
+    /// Issue a token from `cookie` carrying `claims`.
+    pub(crate) fn issue(&self, cookie: &CookieToken, claims: Claims) -> RequestToken {
+        self.issue_with_otp(cookie, self.otp(), claims)
+    }
+
+    /// Issue a token from `cookie` carrying `claims`, with the given pad.
+    pub(crate) fn issue_with_otp(
+        &self,
+        cookie: &CookieToken,
+        otp: [u8; TOKEN_LEN],
+        claims: Claims,
+    ) -> RequestToken {
+        self.audit(|| AuditEvent::TokenIssued {
+            family: cookie.family().audit_id(),
+        });
+        cookie.issue_with_otp(otp, claims)
+    }
+
+    /// Record the outcome of verifying a token from `cookie`.
+    pub(crate) fn audit_outcome(&self, cookie: &CookieToken, outcome: VerifyOutcome) {
+        self.audit(|| {
+            let family = cookie.family().audit_id();
+            match outcome {
+                VerifyOutcome::Valid => AuditEvent::TokenVerified { family },
+                reason => AuditEvent::TokenRejected { family, reason },
+            }
+        });
+    }
+
+    /// The problems with this config on its own.
+    pub(crate) fn config_errors(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        if self.max_uses.is_some() && self.replay_store.is_none() {
+            errors.push(ConfigError::MaxUsesWithoutReplayStore);
+        }
+        if let Some(ttl) = self.ttl {
+            if self.leeway >= ttl {
+                errors.push(ConfigError::LeewayExceedsTtl);
+            }
+            if self.max_lifetime.is_some_and(|m| m < ttl) {
+                errors.push(ConfigError::MaxLifetimeBelowTtl);
+            }
+        }
+        errors
+    }
+
+    /// Whether tokens issued with this config suit a cookie of `scope`.
+    pub(crate) fn matches_scope(&self, scope: &CookieScope) -> bool {
+        self.scope.as_ref().is_none_or(|s| *s == scope.claim())
+    }
+
+    pub(crate) fn has_replay_store(&self) -> bool {
+        self.replay_store.is_some()
+    }
+
+    pub(crate) fn otp(&self) -> [u8; TOKEN_LEN] {
+        self.entropy.otp()
+    }
+
+    pub(crate) fn entropy(&self) -> &Entropy {
+        &self.entropy
+    }
+
+    pub(crate) fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    fn timestamped(&self) -> bool {
+        self.ttl.is_some() || self.max_lifetime.is_some()
+    }
+
+    pub(crate) fn claims(&self) -> Claims {
+        let mut claims = Claims::default();
+        if self.timestamped() {
+            claims.issued_at = Some(self.clock.now());
+        }
+        claims.scope = self.scope.clone();
+        claims.max_uses = self.max_uses;
+        claims.environment = self.environment.clone();
+        claims
+    }
+
+    pub(crate) fn enforce(&self, claims: &Claims) -> Result<()> {
+        if !self.timestamped() {
+            return Ok(());
+        }
+        let now = self.clock.now();
+        let leeway = self.leeway.as_secs();
+        let issued_at = claims.issued_at.ok_or(Error::InvalidToken)?;
+        if issued_at > now.saturating_add(leeway) {
+            return Err(Error::InvalidToken);
+        }
+        let expired = |from: u64, limit: Duration| {
+            now.saturating_sub(from) > limit.as_secs().saturating_add(leeway)
+        };
+        if let Some(ttl) = self.ttl {
+            if expired(issued_at, ttl) {
+                return Err(Error::Expired);
+            }
+        }
+        if let Some(max_lifetime) = self.max_lifetime {
+            let origin = claims.origin.unwrap_or(issued_at);
+            if origin > issued_at || expired(origin, max_lifetime) {
+                return Err(Error::Expired);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fully verify a token: check it is derived from `cookie`, enforce its
+    /// claims, and finally record it as used if tokens are one-time.
+    pub(crate) fn verify(
+        &self,
+        cookie: &CookieToken,
+        token: &RequestToken,
+        purpose: Option<&str>,
+    ) -> Result<()> {
+        self.outcome(cookie, token, purpose).into_result()
+    }
+
+    /// Like `verify`, but evaluating every check before deciding. Only a
+    /// token passing all of them is recorded in the replay store.
+    pub(crate) fn outcome(
+        &self,
+        cookie: &CookieToken,
+        token: &RequestToken,
+        purpose: Option<&str>,
+    ) -> VerifyOutcome {
+        let outcome = self.evaluate(cookie, token, purpose);
+        self.audit_outcome(cookie, outcome);
+        outcome
+    }
+
+    /// Like `outcome`, without recording an audit event.
+    pub(crate) fn evaluate(
+        &self,
+        cookie: &CookieToken,
+        token: &RequestToken,
+        purpose: Option<&str>,
+    ) -> VerifyOutcome {
+        self.evaluate_for(cookie, token, purpose, None)
+    }
+
+    /// Like `evaluate`, also checking the token was minted for `audience`.
+    pub(crate) fn evaluate_for(
+        &self,
+        cookie: &CookieToken,
+        token: &RequestToken,
+        purpose: Option<&str>,
+        audience: Option<&str>,
+    ) -> VerifyOutcome {
+        let checked = cookie.check(token);
+        let enforced = self.enforce(&token.claims);
+        let scoped = purpose.is_none_or(|p| token.claims.purpose.as_deref() == Some(p));
+        let for_audience = audience.is_none_or(|a| token.claims.audience.as_deref() == Some(a));
+        let cookie_scoped = self.scope.is_none() || token.claims.scope == self.scope;
+        let same_environment =
+            self.environment.is_none() || token.claims.environment == self.environment;
+        let revoked = self
+            .revocation_store
+            .as_ref()
+            .is_some_and(|s| s.is_revoked(&cookie.family()));
+        let outcome = match (checked, enforced, scoped, cookie_scoped) {
+            (Err(_), _, _, _) => VerifyOutcome::Mismatch,
+            _ if revoked => VerifyOutcome::Revoked,
+            (_, Err(Error::Expired), _, _) => VerifyOutcome::Expired,
+            (_, Err(_), _, _) => VerifyOutcome::Malformed,
+            (_, _, false, _) => VerifyOutcome::WrongPurpose,
+            (_, _, _, false) => VerifyOutcome::WrongCookieScope,
+            _ if !for_audience => VerifyOutcome::WrongAudience,
+            _ if !same_environment => VerifyOutcome::WrongEnvironment,
+            _ => VerifyOutcome::Valid,
+        };
+        match &self.replay_store {
+            Some(store)
+                if outcome.is_valid()
+                    && !use_nonces(token).any(|n| store.insert(n, self.clock.now())) =>
+            {
+                VerifyOutcome::Replayed
+            }
+            _ => outcome,
+        }
+    }
+}
+
+/// The nonces recording the uses of a token. A token which may be used `n`
+/// times has `n` nonces, and each use records the first one not yet seen.
+/// Single use tokens are recorded by their one-time-pad as is.
+pub(crate) fn use_nonces(token: &RequestToken) -> impl Iterator<Item = Nonce> + '_ {
+    let uses = token.claims.max_uses.unwrap_or(1);
+    (0..uses).map(move |i| {
+        if i == 0 {
+            token.otp
+        } else {
+            sha256::hmac(&token.otp, &[b"xsrf use\0", &[i]])
+        }
+    })
+}
+
+impl CookieToken {
+    /// Generate a request token carrying the claims required by `config`.
+    pub fn gen_req_token_with(&self, config: &XsrfConfig) -> RequestToken {
+        config.issue(self, config.claims())
+    }
+
+    /// Verify the token is derived from this `CookieToken`, and that it
+    /// satisfies `config`.
+    pub fn verify_req_token_with(&self, token: RequestToken, config: &XsrfConfig) -> Result<()> {
+        config.verify(self, &token, None)
+    }
+}
+
+impl RequestToken {
+    /// Verify this token, and issue a fresh one with a new expiry window.
+    /// The renewed token keeps the original issuance time, so
+    /// `with_max_lifetime` still bounds the chain of renewals. One-time
+    /// tokens are used up by renewal.
+    pub fn renew(&self, cookie: &CookieToken, config: &XsrfConfig) -> Result<RequestToken> {
+        config.verify(cookie, self, None)?;
+        let mut claims = config.claims();
+        if claims.issued_at.is_some() {
+            claims.origin = self.claims.origin.or(self.claims.issued_at);
+        }
+        claims.purpose = self.claims.purpose.clone();
+        Ok(config.issue(cookie, claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XsrfConfig;
+    use crate::{
+        CookieScope, CookieToken, Error, MemoryReplayStore, RequestToken, TestClock, VerifyOutcome,
+    };
+    use std::convert::TryInto;
+    use std::time::Duration;
+
+    fn config(clock: &TestClock) -> XsrfConfig {
+        XsrfConfig::new()
+            .with_clock(clock.clone())
+            .with_ttl(Duration::from_secs(60))
+    }
+
+    #[test]
+    fn ttl_round_trip() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock);
+        let ct = CookieToken::new();
+        let s = ct.gen_req_token_with(&config).to_string();
+        let rt: RequestToken = s.as_str().try_into().unwrap();
+        assert_eq!(rt.claims.issued_at, Some(1_000_000));
+        ct.verify_req_token_with(rt, &config).unwrap();
+    }
+
+    #[test]
+    fn ttl_expired() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock);
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        clock.advance(Duration::from_secs(60));
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(ct.verify_req_token_with(rt, &config), Err(Error::Expired));
+    }
+
+    #[test]
+    fn ttl_rejects_future() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock);
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        clock.set(999_999);
+        assert_eq!(
+            ct.verify_req_token_with(rt, &config),
+            Err(Error::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn leeway_tolerates_skew() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock).with_leeway(Duration::from_secs(30));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        clock.set(1_000_000 - 30);
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        clock.set(1_000_000 - 31);
+        assert_eq!(
+            ct.verify_req_token_with(rt.clone(), &config),
+            Err(Error::InvalidToken)
+        );
+        clock.set(1_000_000 + 90);
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(ct.verify_req_token_with(rt, &config), Err(Error::Expired));
+    }
+
+    #[test]
+    fn ttl_requires_timestamp() {
+        let config = config(&TestClock::new(1_000_000));
+        let ct = CookieToken::new();
+        assert_eq!(
+            ct.verify_req_token_with(ct.gen_req_token(), &config),
+            Err(Error::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn renew_keeps_origin() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock).with_max_lifetime(Duration::from_secs(100));
+        let ct = CookieToken::new();
+        let old = ct.gen_req_token_with(&config);
+        clock.advance(Duration::from_secs(50));
+        let renewed = old.renew(&ct, &config).unwrap();
+        assert_eq!(renewed.claims.origin, Some(1_000_000));
+        assert_eq!(renewed.claims.issued_at, Some(1_000_050));
+        clock.advance(Duration::from_secs(50));
+        let renewed = renewed.renew(&ct, &config).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(renewed.renew(&ct, &config).err(), Some(Error::Expired));
+    }
+
+    #[test]
+    fn tampered_claims_mismatch() {
+        let config = config(&TestClock::new(1_000_000));
+        let ct = CookieToken::new();
+        let mut rt = ct.gen_req_token_with(&config);
+        rt.claims.issued_at = rt.claims.issued_at.map(|t| t + 1);
+        assert_eq!(
+            ct.verify_req_token_with(rt, &config),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn replay_store_makes_tokens_one_time() {
+        let clock = TestClock::new(1_000_000);
+        let config =
+            config(&clock).with_replay_store(MemoryReplayStore::new(Duration::from_secs(60)));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        assert_eq!(ct.verify_req_token_with(rt, &config), Err(Error::Replayed));
+    }
+
+    #[test]
+    #[should_panic]
+    fn scope_length_is_checked() {
+        let _ = XsrfConfig::new().with_scope(CookieScope::Domain("a".repeat(256)));
+    }
+
+    #[test]
+    fn scope_is_enforced() {
+        let host = XsrfConfig::new().with_scope(CookieScope::Host);
+        let domain = XsrfConfig::new().with_scope(CookieScope::Domain(".Example.com".to_owned()));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&domain);
+        assert_eq!(rt.claims.scope.as_deref(), Some("example.com"));
+        ct.verify_req_token_with(rt.clone(), &domain).unwrap();
+        assert_eq!(ct.verify_req_token_with(rt, &host), Err(Error::WrongScope));
+        assert_eq!(
+            ct.verify_req_token_with(ct.gen_req_token(), &host),
+            Err(Error::WrongScope)
+        );
+    }
+
+    #[test]
+    fn environment_is_enforced() {
+        let staging = XsrfConfig::new().with_environment("staging");
+        let prod = XsrfConfig::new().with_environment("prod");
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&staging);
+        assert_eq!(rt.claims.environment.as_deref(), Some("staging"));
+        ct.verify_req_token_with(rt.clone(), &staging).unwrap();
+        assert_eq!(
+            ct.verify_req_token_outcome(&rt, &prod),
+            VerifyOutcome::WrongEnvironment
+        );
+        assert_eq!(
+            ct.verify_req_token_with(rt, &prod),
+            Err(Error::WrongEnvironment)
+        );
+        assert_eq!(
+            ct.verify_req_token_with(ct.gen_req_token(), &prod),
+            Err(Error::WrongEnvironment)
+        );
+    }
+
+    #[test]
+    fn max_uses_budget() {
+        let config = XsrfConfig::new()
+            .with_replay_store(MemoryReplayStore::new(Duration::from_secs(60)))
+            .with_max_uses(3);
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        for _ in 0..3 {
+            ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        }
+        assert_eq!(ct.verify_req_token_with(rt, &config), Err(Error::Replayed));
+    }
+
+    #[test]
+    fn stateless_preset_across_instances() {
+        let issuer = XsrfConfig::stateless();
+        let verifier = XsrfConfig::stateless();
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&issuer);
+        assert!(rt.claims.issued_at.is_some());
+        ct.verify_req_token_with(rt.clone(), &verifier).unwrap();
+        ct.verify_req_token_with(rt, &verifier).unwrap();
+    }
+}
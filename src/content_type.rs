@@ -0,0 +1,85 @@
+//! The non-simple request defense.
+//!
+//! Browsers only send cross-site requests without a CORS preflight if they
+//! are "simple": a form content type, and no custom headers. An API only
+//! accepting `application/json`, or requiring a custom header, therefore
+//! only ever sees cross-site requests the CORS policy allowed. A
+//! [`ContentTypeAllowlist`] enforces this, and can be used on its own or
+//! alongside tokens.
+use crate::policy::is_safe_method;
+use crate::request::{media_type, Request};
+use crate::{Error, Result};
+
+/// Rejects state changing requests whose `Content-Type` is not allowed.
+#[derive(Clone, Debug, Default)]
+pub struct ContentTypeAllowlist {
+    allowed: Vec<String>,
+    required_headers: Vec<String>,
+}
+
+impl ContentTypeAllowlist {
+    /// Allow the given media types, such as `application/json`. Parameters
+    /// are ignored.
+    pub fn new(allowed: &[&str]) -> ContentTypeAllowlist {
+        Self {
+            allowed: allowed.iter().map(|t| media_type(t)).collect(),
+            required_headers: Vec::new(),
+        }
+    }
+
+    /// Also require the header `name`, such as `X-Requested-With`, which
+    /// cross-site requests can only carry after a preflight.
+    pub fn with_required_header(mut self, name: &str) -> ContentTypeAllowlist {
+        self.required_headers.push(name.to_owned());
+        self
+    }
+
+    /// Fail with `Error::ContentType` if `req` uses an unsafe method, and
+    /// lacks an allowed `Content-Type` or a required header. Requests
+    /// without a `Content-Type` are rejected too.
+    pub fn check<R: Request + ?Sized>(&self, req: &R) -> Result<()> {
+        if is_safe_method(req.method()) {
+            return Ok(());
+        }
+        let allowed = req
+            .header("content-type")
+            .is_some_and(|ct| self.allowed.contains(&media_type(ct)));
+        let headers = self
+            .required_headers
+            .iter()
+            .all(|h| req.header(h).is_some());
+        if allowed && headers {
+            Ok(())
+        } else {
+            Err(Error::ContentType)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentTypeAllowlist;
+    use crate::request::tests::TestRequest;
+    use crate::Error;
+
+    #[test]
+    fn only_allowed_content_types() {
+        let a = ContentTypeAllowlist::new(&["application/json"])
+            .with_required_header("X-Requested-With");
+        let post = |ct: &str| {
+            TestRequest::new("POST", "/")
+                .header("Content-Type", ct)
+                .header("X-Requested-With", "fetch")
+        };
+        a.check(&post("Application/JSON; charset=utf-8")).unwrap();
+        a.check(&TestRequest::new("GET", "/")).unwrap();
+        let err = Err(Error::ContentType);
+        assert_eq!(a.check(&post("application/x-www-form-urlencoded")), err);
+        assert_eq!(a.check(&post("text/plain")), err);
+        assert_eq!(a.check(&TestRequest::new("POST", "/")), err);
+        assert_eq!(
+            a.check(&TestRequest::new("POST", "/").header("Content-Type", "application/json")),
+            err
+        );
+    }
+}
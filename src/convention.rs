@@ -0,0 +1,132 @@
+//! Presets for the cookie, header and field names used by common stacks.
+//!
+//! Services running beside, or migrating from, another stack often need to
+//! use its names. A [`Convention`] bundles them, so switching is a one-line
+//! change, and hands them to the cookie and extraction helpers.
+use crate::{Extractor, TokenCookie};
+
+/// The names of the token cookie, header and form field.
+///
+/// The default is this crate's own: the `xsrf` cookie and field, and the
+/// `X-XSRF-Token` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Convention {
+    cookie: TokenCookie,
+    header: String,
+    field: String,
+}
+
+impl Default for Convention {
+    fn default() -> Self {
+        Self {
+            cookie: TokenCookie::default(),
+            header: "X-XSRF-Token".to_owned(),
+            field: "xsrf".to_owned(),
+        }
+    }
+}
+
+impl Convention {
+    pub fn new() -> Convention {
+        Self::default()
+    }
+
+    /// The `__Host-xsrf` cookie, which browsers only accept when it is
+    /// `Secure`, has `Path=/` and no `Domain`, so subdomains cannot plant it.
+    pub fn host() -> Convention {
+        Self::new().with_cookie_name("__Host-xsrf")
+    }
+
+    /// The `XSRF-TOKEN` cookie and `X-XSRF-TOKEN` header used by Angular
+    /// and Laravel, with Laravel's `_token` field.
+    pub fn angular() -> Convention {
+        Self::new()
+            .with_cookie_name("XSRF-TOKEN")
+            .with_header("X-XSRF-TOKEN")
+            .with_field("_token")
+    }
+
+    /// The `csrftoken` cookie, `X-CSRFToken` header and
+    /// `csrfmiddlewaretoken` field used by Django.
+    pub fn django() -> Convention {
+        Self::new()
+            .with_cookie_name("csrftoken")
+            .with_header("X-CSRFToken")
+            .with_field("csrfmiddlewaretoken")
+    }
+
+    /// The `_csrf` cookie and field and `CSRF-Token` header used by Express
+    /// apps with `csurf`.
+    pub fn express() -> Convention {
+        Self::new()
+            .with_cookie_name("_csrf")
+            .with_header("CSRF-Token")
+            .with_field("_csrf")
+    }
+
+    /// Use the given cookie, keeping the header and field names.
+    pub fn with_cookie(mut self, cookie: TokenCookie) -> Convention {
+        self.cookie = cookie;
+        self
+    }
+
+    pub fn with_cookie_name(mut self, name: &str) -> Convention {
+        self.cookie.name = name.to_owned();
+        self
+    }
+
+    pub fn with_header(mut self, header: &str) -> Convention {
+        self.header = header.to_owned();
+        self
+    }
+
+    pub fn with_field(mut self, field: &str) -> Convention {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// The token cookie.
+    pub fn cookie(&self) -> &TokenCookie {
+        &self.cookie
+    }
+
+    /// The header carrying the request token.
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// The form field or JSON member carrying the request token.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// An extractor looking for the request token under these names.
+    pub fn extractor(&self) -> Extractor {
+        Extractor::new()
+            .with_header(&self.header)
+            .with_field(&self.field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Convention;
+    use crate::request::tests::TestRequest;
+
+    #[test]
+    fn presets() {
+        let host = Convention::host();
+        host.cookie().check_prefix().unwrap();
+        let django = Convention::django();
+        assert_eq!(django.cookie().name, "csrftoken");
+        let req = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("x-csrftoken", "from-header");
+        let e = django.extractor();
+        assert_eq!(
+            e.extract_token(&req, b"csrfmiddlewaretoken=a").as_deref(),
+            Some("a")
+        );
+        assert_eq!(e.extract_token(&req, b"").as_deref(), Some("from-header"));
+    }
+}
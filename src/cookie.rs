@@ -0,0 +1,477 @@
+//! The cookie carrying the `CookieToken`.
+use crate::sha256::sha256;
+use crate::{CookieToken, Error, Result};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The `SameSite` cookie attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        })
+    }
+}
+
+/// A dangerous cookie configuration.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieError {
+    #[error("xsrf cookie is missing the SameSite attribute")]
+    MissingSameSite,
+    #[error("xsrf cookie uses SameSite=None without Secure")]
+    SameSiteNoneWithoutSecure,
+    #[error("xsrf cookie with the __Secure- prefix must be Secure")]
+    SecurePrefixWithoutSecure,
+    #[error("xsrf cookie with the __Host- prefix must be Secure, have no Domain and Path=/")]
+    InvalidHostPrefix,
+    #[error("xsrf cookie value needs more than 16 chunks")]
+    TooManyChunks,
+}
+
+/// Check the value of an outgoing `Set-Cookie` header carrying the token
+/// for a dangerous `SameSite` configuration, so integrations can fail fast.
+pub fn check_same_site(set_cookie: &str) -> std::result::Result<(), CookieError> {
+    let attrs = attributes(set_cookie);
+    let secure = attrs.iter().any(|(k, _)| k.eq_ignore_ascii_case("secure"));
+    match attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("samesite"))
+    {
+        None => Err(CookieError::MissingSameSite),
+        Some((_, v)) if v.eq_ignore_ascii_case("none") && !secure => {
+            Err(CookieError::SameSiteNoneWithoutSecure)
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// The attributes of a `Set-Cookie` header value, after the name and value.
+fn attributes(set_cookie: &str) -> Vec<(&str, &str)> {
+    set_cookie
+        .split(';')
+        .skip(1)
+        .map(|a| match a.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => (a.trim(), ""),
+        })
+        .collect()
+}
+
+/// Find the value of the cookie named `name` in the value of a `Cookie`
+/// request header, removing any quotes.
+fn find_cookie<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split(';').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+        if k.trim() != name {
+            return None;
+        }
+        let v = v.trim();
+        Some(
+            v.strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(v),
+        )
+    })
+}
+
+impl CookieToken {
+    /// Decode the token from the cookie named `cookie_name` in the value of a
+    /// raw `Cookie` request header. The value may be quoted and
+    /// percent-encoded. A missing cookie is an `Error::InvalidToken`.
+    pub fn from_cookie_header(header_value: &str, cookie_name: &str) -> Result<CookieToken> {
+        let value = find_cookie(header_value, cookie_name).ok_or(Error::InvalidToken)?;
+        let value = crate::percent::decode(value.as_bytes(), false).ok_or(Error::InvalidToken)?;
+        CookieToken::try_from(value.as_str())
+    }
+}
+
+/// The name and attributes of the cookie carrying the `CookieToken`.
+///
+/// The default is a cookie named `xsrf` with `Path=/`, `Secure`, `HttpOnly`
+/// and `SameSite=Lax`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenCookie {
+    pub name: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    pub max_age: Option<u64>,
+}
+
+impl Default for TokenCookie {
+    fn default() -> Self {
+        Self {
+            name: "xsrf".to_owned(),
+            path: Some("/".to_owned()),
+            domain: None,
+            secure: true,
+            http_only: true,
+            same_site: Some(SameSite::Lax),
+            max_age: None,
+        }
+    }
+}
+
+/// Which hosts the token cookie is sent to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CookieScope {
+    /// Only the host that set it, without a `Domain` attribute.
+    Host,
+    /// The domain and all its subdomains.
+    Domain(String),
+}
+
+impl CookieScope {
+    /// The form embedded in tokens: empty for host-only, or the domain in
+    /// lowercase without a leading dot.
+    pub(crate) fn claim(&self) -> String {
+        match self {
+            CookieScope::Host => String::new(),
+            CookieScope::Domain(d) => d.trim_start_matches('.').to_ascii_lowercase(),
+        }
+    }
+}
+
+impl TokenCookie {
+    /// The scope of the cookie, as set by its `domain`.
+    pub fn scope(&self) -> CookieScope {
+        match &self.domain {
+            None => CookieScope::Host,
+            Some(d) => CookieScope::Domain(d.clone()),
+        }
+    }
+
+    /// Make the cookie host-only, or shared with subdomains.
+    pub fn with_scope(mut self, scope: CookieScope) -> TokenCookie {
+        self.domain = match scope {
+            CookieScope::Host => None,
+            CookieScope::Domain(d) => Some(d),
+        };
+        self
+    }
+
+    /// The value of a `Set-Cookie` header setting the cookie to `value`.
+    ///
+    /// Fails if the name uses the `__Secure-` or `__Host-` prefix and the
+    /// attributes do not satisfy its requirements, since browsers would
+    /// silently drop the cookie.
+    pub fn set_cookie_header(&self, value: &str) -> std::result::Result<String, CookieError> {
+        self.check_prefix()?;
+        Ok(self.render(value))
+    }
+
+    /// Check the attributes satisfy the requirements of the name prefix.
+    pub fn check_prefix(&self) -> std::result::Result<(), CookieError> {
+        let has_prefix = |prefix: &str| {
+            self.name
+                .get(..prefix.len())
+                .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+        };
+        if has_prefix("__Secure-") && !self.secure {
+            return Err(CookieError::SecurePrefixWithoutSecure);
+        }
+        if has_prefix("__Host-")
+            && (!self.secure || self.domain.is_some() || self.path.as_deref() != Some("/"))
+        {
+            return Err(CookieError::InvalidHostPrefix);
+        }
+        Ok(())
+    }
+
+    fn render(&self, value: &str) -> String {
+        let mut s = format!("{}={}", self.name, value);
+        if let Some(path) = &self.path {
+            s.push_str("; Path=");
+            s.push_str(path);
+        }
+        if let Some(domain) = &self.domain {
+            s.push_str("; Domain=");
+            s.push_str(domain);
+        }
+        if let Some(max_age) = self.max_age {
+            s.push_str(&format!("; Max-Age={}", max_age));
+        }
+        if self.secure {
+            s.push_str("; Secure");
+        }
+        if self.http_only {
+            s.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            s.push_str(&format!("; SameSite={}", same_site));
+        }
+        s
+    }
+
+    /// Check the cookie for a dangerous `SameSite` configuration.
+    pub fn check_same_site(&self) -> std::result::Result<(), CookieError> {
+        check_same_site(&self.render(""))
+    }
+
+    /// The values of `Set-Cookie` headers setting the cookie to `value`,
+    /// which may be too large for a single cookie.
+    ///
+    /// Values longer than `max_len` are split across cookies named
+    /// `<name>.0`, `<name>.1` and so on, and the unsplit cookie is deleted.
+    /// The first chunk starts with the number of chunks and a digest of the
+    /// whole value, so `chunked_value` can detect missing or stale chunks.
+    /// Values needing more than 16 chunks, which `chunked_value` would never
+    /// accept, fail with `CookieError::TooManyChunks`.
+    ///
+    /// Panics if `max_len` leaves no room for the chunk header.
+    pub fn set_cookie_headers(
+        &self,
+        value: &str,
+        max_len: usize,
+    ) -> std::result::Result<Vec<String>, CookieError> {
+        self.check_prefix()?;
+        if value.len() <= max_len {
+            return Ok(vec![self.render(value)]);
+        }
+        assert!(
+            max_len > CHUNK_HEADER_MAX_LEN,
+            "xsrf cookie chunks too small"
+        );
+        let chunk_len = max_len - CHUNK_HEADER_MAX_LEN;
+        let chunks = split_chars(value, chunk_len);
+        if chunks.len() > MAX_CHUNKS {
+            return Err(CookieError::TooManyChunks);
+        }
+        let mut headers = vec![TokenCookie {
+            max_age: Some(0),
+            ..self.clone()
+        }
+        .render("")];
+        for (i, chunk) in chunks.iter().enumerate() {
+            let chunk_cookie = TokenCookie {
+                name: format!("{}.{}", self.name, i),
+                ..self.clone()
+            };
+            let value = if i == 0 {
+                format!("{}~{}~{}", chunks.len(), chunk_digest(value), chunk)
+            } else {
+                (*chunk).to_owned()
+            };
+            headers.push(chunk_cookie.render(&value));
+        }
+        Ok(headers)
+    }
+
+    /// The value of the cookie in the value of a raw `Cookie` request
+    /// header, reassembling it if it was split by `set_cookie_headers`.
+    /// Chunks that are missing or do not match the digest are an
+    /// `Error::InvalidToken`.
+    pub fn chunked_value(&self, header_value: &str) -> Result<String> {
+        if let Some(value) = find_cookie(header_value, &self.name) {
+            return Ok(value.to_owned());
+        }
+        let first = find_cookie(header_value, &format!("{}.0", self.name));
+        let mut parts = first.ok_or(Error::InvalidToken)?.splitn(3, '~');
+        let (count, digest, mut value) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(c), Some(d), Some(v)) => (c, d, v.to_owned()),
+            _ => return Err(Error::InvalidToken),
+        };
+        let count: usize = count.parse().map_err(|_| Error::InvalidToken)?;
+        if count > MAX_CHUNKS {
+            return Err(Error::InvalidToken);
+        }
+        for i in 1..count {
+            let name = format!("{}.{}", self.name, i);
+            value.push_str(find_cookie(header_value, &name).ok_or(Error::InvalidToken)?);
+        }
+        if chunk_digest(&value) != digest {
+            return Err(Error::InvalidToken);
+        }
+        Ok(value)
+    }
+}
+
+/// Room reserved in the first chunk for the chunk count and digest.
+const CHUNK_HEADER_MAX_LEN: usize = 24;
+
+/// Browsers limit the number of cookies per domain, so more chunks than this
+/// are never valid.
+const MAX_CHUNKS: usize = 16;
+
+/// Split `value` into chunks of at most `len` bytes, on character
+/// boundaries. A character longer than `len` gets a chunk of its own.
+fn split_chars(value: &str, len: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        if i + c.len_utf8() - start > len && i > start {
+            chunks.push(&value[start..i]);
+            start = i;
+        }
+    }
+    chunks.push(&value[start..]);
+    chunks
+}
+
+/// A short digest of a chunked value. This catches truncation and stale
+/// chunks, not tampering, which the token itself protects against.
+fn chunk_digest(value: &str) -> String {
+    sha256(value.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_same_site, CookieError, CookieScope, SameSite, TokenCookie};
+    use crate::{CookieToken, Error};
+
+    #[test]
+    fn set_cookie_header() {
+        assert_eq!(
+            TokenCookie::default().set_cookie_header("abc").unwrap(),
+            "xsrf=abc; Path=/; Secure; HttpOnly; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn prefix_requirements() {
+        let host = TokenCookie {
+            name: "__Host-xsrf".to_owned(),
+            ..TokenCookie::default()
+        };
+        host.check_prefix().unwrap();
+        let with_domain = TokenCookie {
+            domain: Some("example.com".to_owned()),
+            ..host.clone()
+        };
+        assert_eq!(
+            with_domain.set_cookie_header("a"),
+            Err(CookieError::InvalidHostPrefix)
+        );
+        let with_path = TokenCookie {
+            path: Some("/app".to_owned()),
+            ..host
+        };
+        assert_eq!(
+            with_path.check_prefix(),
+            Err(CookieError::InvalidHostPrefix)
+        );
+        let secure = TokenCookie {
+            name: "__secure-xsrf".to_owned(),
+            secure: false,
+            ..TokenCookie::default()
+        };
+        assert_eq!(
+            secure.check_prefix(),
+            Err(CookieError::SecurePrefixWithoutSecure)
+        );
+    }
+
+    #[test]
+    fn same_site_checks() {
+        assert_eq!(check_same_site("a=b; Secure; SameSite=Lax"), Ok(()));
+        assert_eq!(check_same_site("a=b; secure; samesite=none"), Ok(()));
+        assert_eq!(
+            check_same_site("a=b; Path=/"),
+            Err(CookieError::MissingSameSite)
+        );
+        assert_eq!(
+            check_same_site("a=b; SameSite=None"),
+            Err(CookieError::SameSiteNoneWithoutSecure)
+        );
+        let cookie = TokenCookie {
+            secure: false,
+            same_site: Some(SameSite::None),
+            ..TokenCookie::default()
+        };
+        assert_eq!(
+            cookie.check_same_site(),
+            Err(CookieError::SameSiteNoneWithoutSecure)
+        );
+    }
+
+    #[test]
+    fn from_cookie_header() {
+        let ct = CookieToken::new();
+        let encoded = ct.to_string().replace('=', "%3D");
+        let header = format!("theme=dark; xsrf=\"{}\"; xsrf_other=1", encoded);
+        let decoded = CookieToken::from_cookie_header(&header, "xsrf").unwrap();
+        assert_eq!(ct.as_bytes(), decoded.as_bytes());
+        assert_eq!(
+            CookieToken::from_cookie_header("theme=dark", "xsrf").err(),
+            Some(Error::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn with_scope_sets_domain() {
+        let cookie =
+            TokenCookie::default().with_scope(CookieScope::Domain("example.com".to_owned()));
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(
+            cookie.clone().with_scope(CookieScope::Host).scope(),
+            CookieScope::Host
+        );
+    }
+
+    #[test]
+    fn chunked_round_trip() {
+        let cookie = TokenCookie::default();
+        let value = "a".repeat(100) + &"b".repeat(100);
+        let small = cookie.set_cookie_headers("abc", 64).unwrap();
+        assert_eq!(small, ["xsrf=abc; Path=/; Secure; HttpOnly; SameSite=Lax"]);
+
+        let headers = cookie.set_cookie_headers(&value, 64).unwrap();
+        assert!(headers[0].starts_with("xsrf=; Path=/; Max-Age=0;"));
+        let pairs: Vec<&str> = headers[1..]
+            .iter()
+            .map(|h| h.split(';').next().unwrap())
+            .collect();
+        assert_eq!(pairs.len(), 5);
+        let header = pairs.join("; ");
+        assert_eq!(cookie.chunked_value(&header).unwrap(), value);
+
+        let missing = pairs[..4].join("; ");
+        assert_eq!(cookie.chunked_value(&missing), Err(Error::InvalidToken));
+        let stale = header.replace("xsrf.2=a", "xsrf.2=c");
+        assert_eq!(cookie.chunked_value(&stale), Err(Error::InvalidToken));
+    }
+
+    #[test]
+    fn chunks_split_on_char_boundaries() {
+        let cookie = TokenCookie::default();
+        let value = "é".repeat(100);
+        let headers = cookie.set_cookie_headers(&value, 65).unwrap();
+        let pairs: Vec<&str> = headers[1..]
+            .iter()
+            .map(|h| h.split(';').next().unwrap())
+            .collect();
+        assert_eq!(cookie.chunked_value(&pairs.join("; ")).unwrap(), value);
+    }
+
+    #[test]
+    fn chunk_limit() {
+        let cookie = TokenCookie::default();
+        // 40 bytes of each 64 byte chunk are left after the header.
+        let value = "a".repeat(16 * 40);
+        let headers = cookie.set_cookie_headers(&value, 64).unwrap();
+        assert_eq!(headers.len(), 17);
+        let pairs: Vec<&str> = headers[1..]
+            .iter()
+            .map(|h| h.split(';').next().unwrap())
+            .collect();
+        assert_eq!(cookie.chunked_value(&pairs.join("; ")).unwrap(), value);
+        assert_eq!(
+            cookie.set_cookie_headers(&(value + "a"), 64),
+            Err(CookieError::TooManyChunks)
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! A description of the app's CORS policy, for cross-checking it against
+//! the CSRF setup.
+//!
+//! A CORS policy allowing credentialed requests lets the allowed origins
+//! read responses to requests carrying the user's cookies, including pages
+//! embedding request tokens, and then submit them. Any origin an attacker
+//! controls or can inject script into defeats CSRF protection entirely.
+
+/// The parts of a CORS policy relevant to CSRF, checked by
+/// `Validation::cors`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorsPolicy {
+    origins: Vec<String>,
+    reflect_origin: bool,
+    credentials: bool,
+}
+
+impl CorsPolicy {
+    pub fn new() -> CorsPolicy {
+        Self::default()
+    }
+
+    /// Allow the origin, such as `https://app.example.com`.
+    pub fn with_origin(mut self, origin: &str) -> CorsPolicy {
+        self.origins.push(origin.to_ascii_lowercase());
+        self
+    }
+
+    /// Allow any origin by echoing the request `Origin` back in
+    /// `Access-Control-Allow-Origin`.
+    pub fn with_reflected_origin(mut self) -> CorsPolicy {
+        self.reflect_origin = true;
+        self
+    }
+
+    /// Send `Access-Control-Allow-Credentials: true`.
+    pub fn with_credentials(mut self) -> CorsPolicy {
+        self.credentials = true;
+        self
+    }
+
+    pub(crate) fn allows_credentials(&self) -> bool {
+        self.credentials
+    }
+
+    pub(crate) fn reflects_origin(&self) -> bool {
+        self.reflect_origin
+    }
+
+    /// Whether the `null` origin, sent by sandboxed iframes and local files
+    /// any page can create, is allowed.
+    pub(crate) fn allows_null_origin(&self) -> bool {
+        self.origins.iter().any(|o| o == "null")
+    }
+
+    /// Whether a plain `http` origin other than loopback is allowed, whose
+    /// pages a network attacker can inject script into.
+    pub(crate) fn allows_insecure_origin(&self) -> bool {
+        self.origins.iter().any(|o| {
+            o.strip_prefix("http://").is_some_and(|authority| {
+                let host = host(authority);
+                host != "localhost" && host != "127.0.0.1" && host != "[::1]"
+            })
+        })
+    }
+}
+
+/// The host of an origin's `authority`, without the port. IPv6 hosts keep
+/// their brackets.
+fn host(authority: &str) -> &str {
+    match authority.find(']') {
+        Some(end) if authority.starts_with('[') => &authority[..=end],
+        _ => authority.split(':').next().unwrap_or(""),
+    }
+}
@@ -0,0 +1,131 @@
+//! Where the randomness for one-time-pads comes from.
+//!
+//! The default, [`Entropy::ThreadLocal`], is the right choice for almost
+//! everyone. The alternatives trade throughput against how quickly a
+//! compromised generator state stops mattering:
+//!
+//! - `Os` asks the operating system for every pad. Nothing is buffered in
+//!   the process, at the cost of a system call per token.
+//! - `ThreadLocal` uses `rand`'s thread-local ChaCha generator, which the
+//!   operating system reseeds every 64 KiB of output.
+//! - `Reseeding(n)` also uses a thread-local ChaCha generator, reseeded
+//!   after every `n` pads, for deployments wanting a tighter bound. Pads
+//!   are counted per thread, and separately for each `n`, so configs with
+//!   different intervals don't reseed each other's generators.
+//! - `Pool` takes pads from an `OtpPool` filled by a background thread,
+//!   moving the work off the hot path entirely.
+//!
+//! `cargo bench --bench entropy` compares them.
+use crate::{CookieToken, OtpPool, TOKEN_LEN};
+use rand::rngs::{OsRng, StdRng};
+use rand::{thread_rng, RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// A source of randomness for one-time-pads.
+#[derive(Clone, Debug, Default)]
+pub enum Entropy {
+    Os,
+    #[default]
+    ThreadLocal,
+    /// Reseed from the operating system after this many pads, counted per
+    /// thread.
+    Reseeding(u32),
+    Pool(Arc<OtpPool>),
+}
+
+thread_local! {
+    /// A generator and the pads it has produced, for each interval in use.
+    static RESEEDING: RefCell<Vec<(u32, StdRng, u32)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Entropy {
+    pub(crate) fn otp(&self) -> [u8; TOKEN_LEN] {
+        let mut otp = [0; TOKEN_LEN];
+        self.fill(&mut otp);
+        otp
+    }
+
+    /// Fill `out` in one pass. This counts as a single pad for `Reseeding`,
+    /// and pools hand out one pad per `TOKEN_LEN` bytes.
+    pub(crate) fn fill(&self, out: &mut [u8]) {
+        match self {
+            Entropy::Os => OsRng.fill_bytes(out),
+            Entropy::ThreadLocal => thread_rng().fill_bytes(out),
+            Entropy::Reseeding(after) => RESEEDING.with(|states| {
+                let seed = || StdRng::from_rng(OsRng).expect("xsrf: os entropy unavailable");
+                let mut states = states.borrow_mut();
+                let i = match states.iter().position(|(n, ..)| n == after) {
+                    Some(i) => i,
+                    None => {
+                        states.push((*after, seed(), 0));
+                        states.len() - 1
+                    }
+                };
+                let (_, rng, used) = &mut states[i];
+                if *used >= *after {
+                    *rng = seed();
+                    *used = 0;
+                }
+                rng.fill_bytes(out);
+                *used += 1;
+            }),
+            Entropy::Pool(pool) => out
+                .chunks_mut(TOKEN_LEN)
+                .for_each(|c| c.copy_from_slice(&pool.pop()[..c.len()])),
+        }
+    }
+}
+
+impl CookieToken {
+    /// Generate a new token using the given source of randomness.
+    pub fn new_with_entropy(entropy: &Entropy) -> CookieToken {
+        CookieToken::from_bytes(entropy.otp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entropy;
+    use crate::{CookieToken, OtpPool, XsrfConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn every_source_issues_valid_tokens() {
+        let sources = [
+            Entropy::Os,
+            Entropy::ThreadLocal,
+            Entropy::Reseeding(2),
+            Entropy::Pool(Arc::new(OtpPool::new(4))),
+        ];
+        for entropy in sources.iter() {
+            let config = XsrfConfig::new().with_entropy(entropy.clone());
+            let ct = CookieToken::new_with_entropy(entropy);
+            let tokens: Vec<_> = (0..5).map(|_| ct.gen_req_token_with(&config)).collect();
+            assert_ne!(tokens[2].otp, tokens[3].otp);
+            for rt in tokens {
+                ct.verify_req_token_with(rt, &config).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn reseeding_intervals_are_counted_separately() {
+        let (often, rarely) = (Entropy::Reseeding(3), Entropy::Reseeding(1000));
+        for _ in 0..4 {
+            often.otp();
+            rarely.otp();
+        }
+        let used = |n| {
+            super::RESEEDING.with(|states| {
+                let states = states.borrow();
+                states
+                    .iter()
+                    .find(|(m, ..)| *m == n)
+                    .map(|(_, _, used)| *used)
+            })
+        };
+        assert_eq!(used(3), Some(1));
+        assert_eq!(used(1000), Some(4));
+    }
+}
@@ -0,0 +1,364 @@
+//! Extracting request tokens from requests.
+//!
+//! An [`Extractor`] looks for the token in the places a client may send it,
+//! dispatching on the `Content-Type` of the body, so integrations don't each
+//! reimplement this and diverge on the edge cases.
+use crate::request::{media_type, Request};
+use crate::sha256::hmac;
+use crate::{CookieToken, CsrfProtection, Error, RequestToken, Result, Verified};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub(crate) mod form;
+mod json;
+#[cfg(feature = "multipart")]
+mod multipart;
+
+#[cfg(feature = "multipart")]
+pub use multipart::multipart_field;
+
+const FIELD_NAME_DOMAIN: &[u8] = b"xsrf field name\0";
+
+/// A place a request token may be found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The request header.
+    Header,
+    /// The form field or JSON member in the request body, depending on the
+    /// `Content-Type`.
+    Body,
+}
+
+/// Finds request tokens in requests.
+///
+/// By default, the token is looked for in the `xsrf` body field, and then in
+/// the `X-XSRF-Token` header.
+#[derive(Clone, Debug)]
+pub struct Extractor {
+    field: String,
+    header: String,
+    order: Vec<Source>,
+    scan_limit: usize,
+    normalize: bool,
+    normalized: Arc<AtomicU64>,
+    honeypot: Option<String>,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Self {
+            field: "xsrf".to_owned(),
+            header: "X-XSRF-Token".to_owned(),
+            order: vec![Source::Body, Source::Header],
+            scan_limit: 64 * 1024,
+            normalize: false,
+            normalized: Arc::default(),
+            honeypot: None,
+        }
+    }
+}
+
+impl Extractor {
+    pub fn new() -> Extractor {
+        Self::default()
+    }
+
+    /// The name of the form field or JSON member carrying the token.
+    pub fn with_field(mut self, field: &str) -> Extractor {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// Look for the token in the randomized field `cookie.field_name` derives
+    /// from the configured field name. This is per request, since the name
+    /// differs for every `CookieToken`.
+    pub fn with_field_from(mut self, cookie: &CookieToken) -> Extractor {
+        self.field = cookie.field_name(&self.field);
+        self
+    }
+
+    /// The name of the header carrying the token.
+    pub fn with_header(mut self, header: &str) -> Extractor {
+        self.header = header.to_owned();
+        self
+    }
+
+    /// The name of the header carrying the token, `X-XSRF-Token` by default.
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// The sources to look in, in order of priority.
+    pub fn with_order(mut self, order: &[Source]) -> Extractor {
+        self.order = order.to_vec();
+        self
+    }
+
+    /// The maximum number of bytes of a multipart body to scan for the
+    /// token.
+    pub fn with_scan_limit(mut self, scan_limit: usize) -> Extractor {
+        self.scan_limit = scan_limit;
+        self
+    }
+
+    /// Work around buggy clients by trimming ASCII whitespace around the
+    /// token, and percent decoding it once. Valid tokens contain neither, so
+    /// this only changes tokens that would otherwise be rejected, and strict
+    /// parsing still follows.
+    pub fn with_normalization(mut self) -> Extractor {
+        self.normalize = true;
+        self
+    }
+
+    /// Reject submissions filling in the honeypot body field `field`, as
+    /// rendered by `PageSecrets::honeypot_input`, in `verify_any` and
+    /// `check_honeypot`. Submissions without the field are accepted.
+    pub fn with_honeypot(mut self, field: &str) -> Extractor {
+        self.honeypot = Some(field.to_owned());
+        self
+    }
+
+    /// Fail with `Error::Honeypot` if the honeypot field is filled in.
+    pub fn check_honeypot<R: Request + ?Sized>(&self, req: &R, body: &[u8]) -> Result<()> {
+        match &self.honeypot {
+            Some(field)
+                if self
+                    .field_value(req, body, field)
+                    .is_some_and(|v| !v.is_empty()) =>
+            {
+                Err(Error::Honeypot)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How many extracted tokens needed normalization, to gauge how common
+    /// the buggy clients are. Clones of the extractor share the count.
+    pub fn normalized_count(&self) -> u64 {
+        self.normalized.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn normalizes(&self) -> bool {
+        self.normalize
+    }
+
+    /// Find the request token in the request with the given body.
+    pub fn extract_token<R: Request + ?Sized>(&self, req: &R, body: &[u8]) -> Option<String> {
+        let token = self.order.iter().find_map(|source| match source {
+            Source::Header => req.header(&self.header).map(str::to_owned),
+            Source::Body => self.body_field(req, body),
+        })?;
+        Some(self.normalized(token))
+    }
+
+    /// Every request token candidate in the request, in order of priority,
+    /// along with where it was found. Urlencoded bodies may carry several,
+    /// such as from nested forms.
+    pub fn candidates<R: Request + ?Sized>(&self, req: &R, body: &[u8]) -> Vec<(Source, String)> {
+        let mut out = Vec::new();
+        for &source in self.order.iter() {
+            let found = match source {
+                Source::Header => req
+                    .header(&self.header)
+                    .map(str::to_owned)
+                    .into_iter()
+                    .collect(),
+                Source::Body => match req.header("content-type").map(media_type).as_deref() {
+                    Some("application/x-www-form-urlencoded") => {
+                        form::form_fields(body, &self.field)
+                    }
+                    _ => self.body_field(req, body).into_iter().collect(),
+                },
+            };
+            out.extend(found.into_iter().map(|t| (source, self.normalized(t))));
+        }
+        out
+    }
+
+    /// Verify the candidates in the request in turn, accepting if any one
+    /// verifies, and returning it along with where it was found. Fails with
+    /// the error of the last candidate, or `Error::InvalidToken` if there
+    /// were none, after checking the honeypot.
+    pub fn verify_any<R: Request + ?Sized>(
+        &self,
+        req: &R,
+        body: &[u8],
+        protection: &dyn CsrfProtection,
+        cookie: &CookieToken,
+    ) -> Result<(Source, Verified<RequestToken>)> {
+        self.check_honeypot(req, body)?;
+        let mut result = Err(Error::InvalidToken);
+        for (source, token) in self.candidates(req, body) {
+            let verified = RequestToken::try_from(token.as_str())
+                .and_then(|rt| protection.verified(cookie, rt));
+            match verified {
+                Ok(verified) => return Ok((source, verified)),
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+
+    fn normalized(&self, token: String) -> String {
+        if !self.normalize {
+            return token;
+        }
+        let trimmed = token.trim_ascii();
+        let normalized = if trimmed.contains('%') {
+            crate::percent::decode(trimmed.as_bytes(), false).unwrap_or_else(|| trimmed.to_owned())
+        } else {
+            trimmed.to_owned()
+        };
+        if normalized != token {
+            self.normalized.fetch_add(1, Ordering::Relaxed);
+        }
+        normalized
+    }
+
+    fn body_field<R: Request + ?Sized>(&self, req: &R, body: &[u8]) -> Option<String> {
+        self.field_value(req, body, &self.field)
+    }
+
+    fn field_value<R: Request + ?Sized>(
+        &self,
+        req: &R,
+        body: &[u8],
+        field: &str,
+    ) -> Option<String> {
+        let content_type = req.header("content-type")?;
+        match media_type(content_type).as_str() {
+            "application/x-www-form-urlencoded" => form::form_field(body, field),
+            #[cfg(feature = "multipart")]
+            "multipart/form-data" => multipart_field(content_type, body, field, self.scan_limit),
+            t if t == "application/json" || t.ends_with("+json") => json::json_field(body, field),
+            _ => None,
+        }
+    }
+}
+
+impl CookieToken {
+    /// A form field name derived from this token and `prefix`, such as
+    /// `xsrf-3q2ZkR0cTkWG`. It is stable for the session, but differs between
+    /// sessions, which defeats tools looking for a fixed field name. It is
+    /// not a secret, and the request token's one-time-pad is what protects
+    /// against BREACH. Use `Extractor::with_field_from` to find it.
+    pub fn field_name(&self, prefix: &str) -> String {
+        let mac = hmac(&self.data, &[FIELD_NAME_DOMAIN, prefix.as_bytes()]);
+        let mut name = format!("{}-", prefix);
+        base64::encode_config_buf(&mac[..9], base64::URL_SAFE_NO_PAD, &mut name);
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Extractor, Source};
+    use crate::request::tests::TestRequest;
+    use crate::{CookieToken, Error, Plain};
+
+    #[test]
+    fn dispatches_on_content_type() {
+        let e = Extractor::new();
+        let form = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        assert_eq!(e.extract_token(&form, b"xsrf=a").as_deref(), Some("a"));
+        let json = TestRequest::new("POST", "/").header("Content-Type", "application/json");
+        assert_eq!(
+            e.extract_token(&json, br#"{"xsrf":"b"}"#).as_deref(),
+            Some("b")
+        );
+        let text = TestRequest::new("POST", "/").header("Content-Type", "text/plain");
+        assert_eq!(e.extract_token(&text, b"xsrf=a"), None);
+    }
+
+    #[test]
+    fn respects_order() {
+        let req = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("x-xsrf-token", "header");
+        let e = Extractor::new();
+        assert_eq!(e.extract_token(&req, b"xsrf=body").as_deref(), Some("body"));
+        assert_eq!(e.extract_token(&req, b"").as_deref(), Some("header"));
+        let e = e.with_order(&[Source::Header, Source::Body]);
+        assert_eq!(
+            e.extract_token(&req, b"xsrf=body").as_deref(),
+            Some("header")
+        );
+    }
+
+    #[test]
+    fn randomized_field_name() {
+        let ct = CookieToken::new();
+        let name = ct.field_name("xsrf");
+        assert_eq!(name.len(), "xsrf-".len() + 12);
+        assert_eq!(name, ct.field_name("xsrf"));
+        assert_ne!(name, CookieToken::new().field_name("xsrf"));
+        let req = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        let body = format!("xsrf=fixed&{}=random", name);
+        let e = Extractor::new().with_field_from(&ct);
+        assert_eq!(
+            e.extract_token(&req, body.as_bytes()).as_deref(),
+            Some("random")
+        );
+    }
+
+    #[test]
+    fn normalization_is_opt_in() {
+        let req = TestRequest::new("POST", "/").header("X-XSRF-Token", " abc%3D\n");
+        assert_eq!(
+            Extractor::new().extract_token(&req, b"").as_deref(),
+            Some(" abc%3D\n")
+        );
+        let e = Extractor::new().with_normalization();
+        assert_eq!(e.extract_token(&req, b"").as_deref(), Some("abc="));
+        let clean = TestRequest::new("POST", "/").header("X-XSRF-Token", "abc=");
+        assert_eq!(e.extract_token(&clean, b"").as_deref(), Some("abc="));
+        assert_eq!(e.clone().normalized_count(), 1);
+        let signed = TestRequest::new("POST", "/").header("X-XSRF-Token", "abc%+1");
+        assert_eq!(e.extract_token(&signed, b"").as_deref(), Some("abc%+1"));
+    }
+
+    #[test]
+    fn verify_any_candidate() {
+        let ct = CookieToken::new();
+        let good = ct.gen_req_token().to_string().replace('=', "%3D");
+        let req = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-XSRF-Token", "stale");
+        let body = format!("xsrf=stale&xsrf={}", good);
+        let e = Extractor::new();
+        assert_eq!(e.candidates(&req, body.as_bytes()).len(), 3);
+        assert_eq!(
+            e.verify_any(&req, body.as_bytes(), &Plain, &ct)
+                .map(|(source, _)| source),
+            Ok(Source::Body)
+        );
+        assert_eq!(
+            e.verify_any(&req, b"xsrf=stale", &Plain, &ct).err(),
+            Some(Error::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn honeypot_filled() {
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token();
+        let req = TestRequest::new("POST", "/")
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        let e = Extractor::new().with_honeypot("website");
+        for body in [format!("xsrf={}", rt), format!("xsrf={}&website=", rt)] {
+            assert_eq!(
+                e.verify_any(&req, body.as_bytes(), &Plain, &ct)
+                    .map(|(source, _)| source),
+                Ok(Source::Body)
+            );
+        }
+        let body = format!("xsrf={}&website=spam", rt);
+        assert_eq!(
+            e.verify_any(&req, body.as_bytes(), &Plain, &ct).err(),
+            Some(Error::Honeypot)
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! `application/x-www-form-urlencoded` bodies.
+use crate::{Error, Result};
+
+/// Find the value of `field` in a urlencoded body.
+pub(crate) fn form_field(body: &[u8], field: &str) -> Option<String> {
+    body.split(|&b| b == b'&').find_map(|pair| {
+        let mut kv = pair.splitn(2, |&b| b == b'=');
+        let k = decode(kv.next()?)?;
+        if k != field {
+            return None;
+        }
+        decode(kv.next().unwrap_or(b""))
+    })
+}
+
+/// Every value of `field` in a urlencoded body, such as from nested forms,
+/// skipping malformed ones.
+pub(crate) fn form_fields(body: &[u8], field: &str) -> Vec<String> {
+    body.split(|&b| b == b'&')
+        .filter_map(|pair| {
+            let mut kv = pair.splitn(2, |&b| b == b'=');
+            if decode(kv.next()?)? != field {
+                return None;
+            }
+            decode(kv.next().unwrap_or(b""))
+        })
+        .collect()
+}
+
+/// The only value of `field` in a urlencoded body, if any. Fails if the field
+/// is repeated, or if it or any other name is malformed, since other parsers
+/// might see a different value than this one.
+pub(crate) fn sole_form_field(body: &[u8], field: &str) -> Result<Option<String>> {
+    let mut found = None;
+    for pair in body.split(|&b| b == b'&') {
+        let mut kv = pair.splitn(2, |&b| b == b'=');
+        let k = kv.next().and_then(decode).ok_or(Error::InvalidToken)?;
+        if k != field {
+            continue;
+        }
+        let v = decode(kv.next().unwrap_or(b"")).ok_or(Error::InvalidToken)?;
+        if found.replace(v).is_some() {
+            return Err(Error::InvalidToken);
+        }
+    }
+    Ok(found)
+}
+
+fn decode(input: &[u8]) -> Option<String> {
+    crate::percent::decode(input, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{form_field, form_fields, sole_form_field};
+    use crate::Error;
+
+    #[test]
+    fn finds_field() {
+        let body = b"a=1&xsrf=ab%3D%3D&b+c=d";
+        assert_eq!(form_field(body, "xsrf").as_deref(), Some("ab=="));
+        assert_eq!(form_field(body, "b c").as_deref(), Some("d"));
+        assert_eq!(form_field(body, "missing"), None);
+        assert_eq!(form_field(b"xsrf=%zz", "xsrf"), None);
+        assert_eq!(
+            form_fields(b"xsrf=a&b=1&xsrf=%zz&xsrf=c", "xsrf"),
+            ["a", "c"]
+        );
+        assert_eq!(sole_form_field(body, "xsrf"), Ok(Some("ab==".to_owned())));
+        assert_eq!(sole_form_field(body, "missing"), Ok(None));
+        for body in [&b"to=a&to=b"[..], b"to=a&to=%zz", b"to=a&t%zz=b"] {
+            assert_eq!(sole_form_field(body, "to"), Err(Error::InvalidToken));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! JSON bodies. Only string members of the top level object are supported,
+//! and other values are skipped without being interpreted.
+
+/// Find the string value of the top level member `field`.
+pub(crate) fn json_field(body: &[u8], field: &str) -> Option<String> {
+    let mut p = Parser { s: body, i: 0 };
+    p.ws();
+    p.eat(b'{')?;
+    p.ws();
+    if p.peek() == Some(b'}') {
+        return None;
+    }
+    loop {
+        p.ws();
+        let key = p.string()?;
+        p.ws();
+        p.eat(b':')?;
+        p.ws();
+        if key == field && p.peek() == Some(b'"') {
+            return p.string();
+        }
+        p.skip_value(0)?;
+        p.ws();
+        match p.next()? {
+            b',' => continue,
+            _ => return None,
+        }
+    }
+}
+
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.i).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.i += 1;
+        Some(b)
+    }
+
+    fn eat(&mut self, b: u8) -> Option<()> {
+        if self.next()? == b {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.i += 1;
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.eat(b'"')?;
+        let mut out = String::new();
+        loop {
+            match self.next()? {
+                b'"' => return Some(out),
+                b'\\' => match self.next()? {
+                    b'"' => out.push('"'),
+                    b'\\' => out.push('\\'),
+                    b'/' => out.push('/'),
+                    b'b' => out.push('\u{8}'),
+                    b'f' => out.push('\u{c}'),
+                    b'n' => out.push('\n'),
+                    b'r' => out.push('\r'),
+                    b't' => out.push('\t'),
+                    b'u' => {
+                        let hex = self.s.get(self.i..self.i + 4)?;
+                        let c = u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+                        self.i += 4;
+                        // surrogate pairs are never part of a valid token
+                        out.push(char::from_u32(c)?);
+                    }
+                    _ => return None,
+                },
+                b if b < 0x20 => return None,
+                b if b < 0x80 => out.push(b as char),
+                _ => {
+                    // copy the rest of a multi-byte utf-8 sequence as is
+                    let start = self.i - 1;
+                    while matches!(self.peek(), Some(b) if b & 0xc0 == 0x80) {
+                        self.i += 1;
+                    }
+                    out.push_str(std::str::from_utf8(&self.s[start..self.i]).ok()?);
+                }
+            }
+        }
+    }
+
+    fn skip_value(&mut self, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match self.peek()? {
+            b'"' => self.string().map(|_| ()),
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.i += 1;
+                self.ws();
+                if self.peek() == Some(close) {
+                    self.i += 1;
+                    return Some(());
+                }
+                loop {
+                    self.ws();
+                    if open == b'{' {
+                        self.string()?;
+                        self.ws();
+                        self.eat(b':')?;
+                        self.ws();
+                    }
+                    self.skip_value(depth + 1)?;
+                    self.ws();
+                    match self.next()? {
+                        b',' => continue,
+                        b if b == close => return Some(()),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => {
+                let start = self.i;
+                while matches!(
+                    self.peek(),
+                    Some(b'a'..=b'z' | b'0'..=b'9' | b'-' | b'+' | b'.' | b'E')
+                ) {
+                    self.i += 1;
+                }
+                if self.i == start {
+                    None
+                } else {
+                    Some(())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_field;
+
+    #[test]
+    fn finds_top_level_string() {
+        let body = br#" { "a": [1, {"xsrf": "nested"}], "b": null, "xsrf" : "ab==" } "#;
+        assert_eq!(json_field(body, "xsrf").as_deref(), Some("ab=="));
+        assert_eq!(json_field(body, "missing"), None);
+        assert_eq!(json_field(br#"{"xsrf": 1}"#, "xsrf"), None);
+        assert_eq!(json_field(br#"{"xsrf": "#, "xsrf"), None);
+        assert_eq!(json_field(b"[]", "xsrf"), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! A minimal `multipart/form-data` parser, only capable of finding a single
+//! text field.
+
+/// Find the value of the text field named `field` in a `multipart/form-data`
+/// body, given the value of the `Content-Type` header.
+///
+/// Only the first `scan_limit` bytes of the body are considered, so the
+/// token field should be placed before any file inputs in the form. File
+/// parts are never returned.
+pub fn multipart_field(
+    content_type: &str,
+    body: &[u8],
+    field: &str,
+    scan_limit: usize,
+) -> Option<String> {
+    let boundary = boundary(content_type)?;
+    let delimiter = format!("--{}", boundary);
+    let body = &body[..body.len().min(scan_limit)];
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    loop {
+        // the final delimiter is followed by "--" instead of a line break
+        rest = rest.strip_prefix(b"\r\n")?;
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).ok()?;
+        rest = &rest[headers_end + 4..];
+        let end = find(rest, format!("\r\n{}", delimiter).as_bytes())?;
+        if is_field(headers, field) {
+            return String::from_utf8(rest[..end].to_vec()).ok();
+        }
+        rest = &rest[end + 2 + delimiter.len()..];
+    }
+}
+
+fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    params.find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        if !k.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let v = v.trim();
+        let v = v
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(v);
+        if v.is_empty() || v.len() > 70 {
+            None
+        } else {
+            Some(v)
+        }
+    })
+}
+
+/// Whether the part headers describe the text field `field`.
+fn is_field(headers: &str, field: &str) -> bool {
+    headers.split("\r\n").any(|h| {
+        let (name, value) = match h.split_once(':') {
+            Some(v) => v,
+            None => return false,
+        };
+        if !name.trim().eq_ignore_ascii_case("content-disposition") {
+            return false;
+        }
+        let mut params = value.split(';').map(str::trim);
+        if !params
+            .next()
+            .is_some_and(|d| d.eq_ignore_ascii_case("form-data"))
+        {
+            return false;
+        }
+        let mut named = false;
+        for p in params {
+            match p.split_once('=') {
+                Some((k, v)) if k.trim().eq_ignore_ascii_case("name") => {
+                    named = v.trim().trim_matches('"') == field;
+                }
+                Some((k, _)) if k.trim().eq_ignore_ascii_case("filename") => return false,
+                _ => {}
+            }
+        }
+        named
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::multipart_field;
+
+    const CT: &str = "multipart/form-data; boundary=\"XyZ\"";
+    const BODY: &[u8] = b"--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        hello\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"xsrf\"; filename=\"x.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        not-this\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"xsrf\"\r\n\r\n\
+        the-token\r\n\
+        --XyZ--\r\n";
+
+    #[test]
+    fn finds_text_field() {
+        assert_eq!(
+            multipart_field(CT, BODY, "xsrf", 4096).as_deref(),
+            Some("the-token")
+        );
+        assert_eq!(
+            multipart_field(CT, BODY, "title", 4096).as_deref(),
+            Some("hello")
+        );
+        assert_eq!(multipart_field(CT, BODY, "missing", 4096), None);
+    }
+
+    #[test]
+    fn respects_scan_limit() {
+        assert_eq!(multipart_field(CT, BODY, "xsrf", 100), None);
+    }
+
+    #[test]
+    fn rejects_other_content_types() {
+        assert_eq!(
+            multipart_field("application/json; boundary=XyZ", BODY, "xsrf", 4096),
+            None
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Revoking every token of a user or device class at once.
+//!
+//! A `CookieToken` issued with `CookieToken::new_in_family` carries the
+//! random [`TokenFamily`] identifier alongside its own random bytes, and
+//! revoking the family in a [`RevocationStore`] rejects every token in it,
+//! such as when signing out other devices.
+//!
+//! The family is not part of the token's bytes or its encoding, so it reveals
+//! nothing about the secret and clients can't choose it. Store it alongside
+//! the token, such as with the user in the session, and attach it again with
+//! `CookieToken::with_family` after decoding the token. Tokens without one,
+//! such as those from `new`, are each in a family of their own, derived one
+//! way from the token.
+use crate::sha256::hmac;
+use crate::{AuditEvent, AuditSink, Base64Url, CookieToken, Error, TokenCodec, TOKEN_LEN};
+use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Length of the raw bytes of a `TokenFamily`.
+pub const TOKEN_FAMILY_BYTES: usize = 16;
+
+const DOMAIN: &[u8] = b"xsrf token family\0";
+
+/// Identifies a family of cookie tokens that can be revoked together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TokenFamily {
+    data: [u8; TOKEN_FAMILY_BYTES],
+}
+
+impl TokenFamily {
+    /// A new random family.
+    pub fn new() -> TokenFamily {
+        let mut data = [0; TOKEN_FAMILY_BYTES];
+        thread_rng().fill(&mut data);
+        Self { data }
+    }
+
+    /// Construct a family from its raw bytes.
+    pub const fn from_bytes(data: [u8; TOKEN_FAMILY_BYTES]) -> TokenFamily {
+        Self { data }
+    }
+
+    /// The raw bytes of the family.
+    pub const fn as_bytes(&self) -> &[u8; TOKEN_FAMILY_BYTES] {
+        &self.data
+    }
+}
+
+impl Default for TokenFamily {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TokenFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::with_capacity(Base64Url.encoded_len(TOKEN_FAMILY_BYTES));
+        Base64Url.encode(&self.data, &mut s);
+        f.write_str(&s)
+    }
+}
+
+impl std::convert::TryFrom<&str> for TokenFamily {
+    type Error = Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let mut data = [0; TOKEN_FAMILY_BYTES];
+        Base64Url.decode(value, &mut data)?;
+        Ok(Self { data })
+    }
+}
+
+impl CookieToken {
+    /// Generate a new random token in the given family.
+    pub fn new_in_family(family: &TokenFamily) -> CookieToken {
+        CookieToken::new().with_family(family)
+    }
+
+    /// Place this token in the given family, such as the one stored with it
+    /// after decoding it from a cookie.
+    pub fn with_family(mut self, family: &TokenFamily) -> CookieToken {
+        self.family = Some(*family);
+        self
+    }
+
+    /// The family this token belongs to. Tokens derived from another, such
+    /// as by `bind`, belong to the family of the token they were derived
+    /// from. Tokens without a family are in one of their own, derived one
+    /// way from the token.
+    pub fn family(&self) -> TokenFamily {
+        if let Some(family) = self.family {
+            return family;
+        }
+        let mut data = [0; TOKEN_FAMILY_BYTES];
+        data.copy_from_slice(&hmac(&self.data, &[DOMAIN])[..TOKEN_FAMILY_BYTES]);
+        TokenFamily { data }
+    }
+
+    /// A token derived from this one, which stays in this token's family so
+    /// revoking the family still rejects it.
+    pub(crate) fn derive_child(&self, data: [u8; TOKEN_LEN]) -> CookieToken {
+        let mut child = CookieToken::from_bytes(data);
+        child.family = Some(self.family());
+        child
+    }
+}
+
+/// Records revoked token families. This is consulted on every verification,
+/// so lookups should be cheap.
+pub trait RevocationStore: fmt::Debug + Send + Sync {
+    fn is_revoked(&self, family: &TokenFamily) -> bool;
+}
+
+/// An in-memory revocation store.
+#[derive(Debug, Default)]
+pub struct MemoryRevocationStore {
+    revoked: RwLock<HashSet<TokenFamily>>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> MemoryRevocationStore {
+        Self::default()
+    }
+
+    /// Record an `AuditEvent::TokenRevoked` in the given sink for every
+    /// revocation.
+    pub fn with_audit_sink<S: AuditSink + 'static>(mut self, sink: Arc<S>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+
+    /// Revoke every token in `family`.
+    pub fn revoke(&self, family: TokenFamily) {
+        let mut revoked = self.revoked.write().unwrap_or_else(|e| e.into_inner());
+        revoked.insert(family);
+        if let Some(sink) = &self.audit_sink {
+            sink.record(AuditEvent::TokenRevoked {
+                family: family.audit_id(),
+            });
+        }
+    }
+}
+
+impl RevocationStore for MemoryRevocationStore {
+    fn is_revoked(&self, family: &TokenFamily) -> bool {
+        let revoked = self.revoked.read().unwrap_or_else(|e| e.into_inner());
+        revoked.contains(family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryRevocationStore, TokenFamily};
+    use crate::{CookieToken, Error, XsrfConfig};
+    use std::convert::TryFrom;
+    use std::sync::Arc;
+
+    #[test]
+    fn revoke_family() {
+        let store = Arc::new(MemoryRevocationStore::new());
+        let config = XsrfConfig::new().with_revocation_store(store.clone());
+        let family = TokenFamily::new();
+        let laptop = CookieToken::new_in_family(&family);
+        let phone = CookieToken::new_in_family(&family);
+        let other = CookieToken::new();
+        assert_eq!(phone.family(), family);
+        assert_ne!(&phone.as_bytes()[..16], family.as_bytes());
+        let tokens = [&laptop, &phone, &other].map(|ct| (ct, ct.gen_req_token_with(&config)));
+        for (ct, rt) in tokens.iter() {
+            ct.verify_req_token_with(rt.clone(), &config).unwrap();
+        }
+        store.revoke(family);
+        for (ct, rt) in tokens[..2].iter() {
+            let got = ct.verify_req_token_with(rt.clone(), &config);
+            assert_eq!(got, Err(Error::Revoked));
+        }
+        let (ct, rt) = &tokens[2];
+        ct.verify_req_token_with(rt.clone(), &config).unwrap();
+
+        // the family doesn't survive encoding, and must be attached again.
+        let decoded = CookieToken::try_from(phone.to_string().as_str()).unwrap();
+        assert_ne!(decoded.family(), family);
+        let restored = decoded.with_family(&family);
+        let got = restored.verify_req_token_with(restored.gen_req_token(), &config);
+        assert_eq!(got, Err(Error::Revoked));
+    }
+}
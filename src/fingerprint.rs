@@ -0,0 +1,96 @@
+//! Server side storage of a hash of the `CookieToken`.
+//!
+//! When using the synchronizer pattern, the `CookieToken` lives in the server
+//! side session store. Storing a `Fingerprint` instead means a leak of the
+//! store does not leak usable tokens.
+use crate::sha256::{sha256, DIGEST_LEN};
+use crate::{Base64Url, CookieToken, Error, RequestToken, Result, TokenCodec, TOKEN_LEN};
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+const DOMAIN: &[u8] = b"xsrf fingerprint v1\0";
+
+/// A SHA-256 based fingerprint of a `CookieToken`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    data: [u8; DIGEST_LEN],
+}
+
+fn digest(data: &[u8; TOKEN_LEN]) -> [u8; DIGEST_LEN] {
+    let mut input = [0; DOMAIN.len() + TOKEN_LEN];
+    input[..DOMAIN.len()].copy_from_slice(DOMAIN);
+    input[DOMAIN.len()..].copy_from_slice(data);
+    sha256(&input)
+}
+
+impl CookieToken {
+    /// Compute the fingerprint of this token.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint {
+            data: digest(&self.data),
+        }
+    }
+}
+
+impl Fingerprint {
+    /// Construct a fingerprint from its raw bytes.
+    pub const fn from_bytes(data: [u8; DIGEST_LEN]) -> Fingerprint {
+        Self { data }
+    }
+
+    /// The raw bytes of the fingerprint.
+    pub const fn as_bytes(&self) -> &[u8; DIGEST_LEN] {
+        &self.data
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::with_capacity(Base64Url.encoded_len(DIGEST_LEN));
+        Base64Url.encode(&self.data, &mut s);
+        f.write_str(&s)
+    }
+}
+
+impl std::convert::TryFrom<&str> for Fingerprint {
+    type Error = Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let mut data = [0; DIGEST_LEN];
+        Base64Url.decode(value, &mut data)?;
+        Ok(Self { data })
+    }
+}
+
+/// Verify a `RequestToken` against the `Fingerprint` of the `CookieToken` it
+/// was generated from. Only tokens without claims can be verified this way,
+/// since verifying claims requires the `CookieToken` itself.
+pub fn verify_req_token_against_fingerprint(fp: &Fingerprint, token: RequestToken) -> Result<()> {
+    if !token.claims.is_empty() {
+        return Err(Error::InvalidToken);
+    }
+    let mut expected = [0; TOKEN_LEN];
+    crate::xor_into(&token.otp, &token.mask, &mut expected);
+    let eq: bool = digest(&expected).ct_eq(&fp.data).into();
+    if eq {
+        Ok(())
+    } else {
+        Err(Error::TokenMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_req_token_against_fingerprint, Fingerprint};
+    use crate::CookieToken;
+    use std::convert::TryInto;
+
+    #[test]
+    fn verify_against_fingerprint() {
+        let ct = CookieToken::new();
+        let fp: Fingerprint = ct.fingerprint().to_string().as_str().try_into().unwrap();
+        verify_req_token_against_fingerprint(&fp, ct.gen_req_token()).unwrap();
+        let other = CookieToken::new();
+        assert!(verify_req_token_against_fingerprint(&fp, other.gen_req_token()).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Startup checks for paranoid deployments.
+use crate::claims::Claims;
+use crate::{CookieToken, RequestToken, XsrfConfig, TOKEN_LEN};
+use std::convert::TryFrom;
+
+/// Why `self_test` failed.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestError {
+    #[error("xsrf entropy source produced degenerate output")]
+    DegenerateEntropy,
+    #[error("xsrf token failed to round trip")]
+    RoundTrip,
+}
+
+/// Check that the entropy source configured in `config` is producing
+/// non-degenerate output, and that tokens survive encoding, decoding and
+/// verification. This is meant to be run once at startup, refusing to serve
+/// if it fails. It records no audit events.
+///
+/// This only catches gross failures, such as a source returning zeros or
+/// repeating itself. It cannot prove the output is unpredictable.
+pub fn self_test(config: &XsrfConfig) -> Result<(), SelfTestError> {
+    let entropy = config.entropy();
+    check(|| entropy.otp())
+}
+
+/// Run the self-test with bytes from `next`.
+fn check(mut next: impl FnMut() -> [u8; TOKEN_LEN]) -> Result<(), SelfTestError> {
+    let a = CookieToken::from_bytes(next());
+    let b = CookieToken::from_bytes(next());
+    let ra = a.issue_with_otp(next(), Claims::default());
+    let rb = a.issue_with_otp(next(), Claims::default());
+    let degenerate = |d: &[u8]| d.iter().all(|&x| x == d[0]);
+    if a.as_bytes() == b.as_bytes()
+        || ra.otp == rb.otp
+        || degenerate(a.as_bytes())
+        || degenerate(&ra.otp)
+    {
+        return Err(SelfTestError::DegenerateEntropy);
+    }
+
+    let round_trip = || -> crate::Result<()> {
+        let ct = CookieToken::try_from(a.to_string().as_str())?;
+        ct.verify_req_token(RequestToken::try_from(ra.to_string().as_str())?)?;
+        let claimed = ct.issue_with_otp(
+            next(),
+            Claims {
+                issued_at: Some(1),
+                ..Claims::default()
+            },
+        );
+        ct.verify_req_token(RequestToken::try_from(claimed.to_string().as_str())?)?;
+        if b.verify_req_token(rb).is_ok() {
+            return Err(crate::Error::InvalidToken);
+        }
+        Ok(())
+    };
+    round_trip().map_err(|_| SelfTestError::RoundTrip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, self_test, SelfTestError};
+    use crate::{Entropy, OtpPool, XsrfConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn self_test_passes() {
+        for entropy in [
+            Entropy::Os,
+            Entropy::ThreadLocal,
+            Entropy::Reseeding(2),
+            Entropy::Pool(Arc::new(OtpPool::new(4))),
+        ] {
+            self_test(&XsrfConfig::new().with_entropy(entropy)).unwrap();
+        }
+    }
+
+    #[test]
+    fn degenerate_sources_fail() {
+        assert_eq!(check(|| [0; 32]), Err(SelfTestError::DegenerateEntropy));
+        let mut i = 0;
+        let repeating = || {
+            i += 1;
+            [i % 2; 32]
+        };
+        assert_eq!(check(repeating), Err(SelfTestError::DegenerateEntropy));
+    }
+}
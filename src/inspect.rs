@@ -0,0 +1,156 @@
+//! Non-secret introspection of encoded tokens.
+//!
+//! This is meant for debugging endpoints and support tooling, and never
+//! performs verification. Nothing reported here depends on the secret parts
+//! of a token.
+use crate::claims::{self, Claims};
+use crate::{Base64Url, RequestToken, TokenCodec, TOKEN_LEN};
+
+/// The version of the original token format, without claims.
+pub const PLAIN_FORMAT_VERSION: u8 = 1;
+/// The version of the token format carrying claims.
+pub const CLAIMS_FORMAT_VERSION: u8 = claims::VERSION;
+
+/// The textual encoding a token was found to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// URL safe base64 with padding.
+    Base64Url,
+}
+
+/// Metadata about an encoded `RequestToken`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TokenInfo {
+    /// Length of the encoded token.
+    pub len: usize,
+    /// The format version, if the token was recognized.
+    pub version: Option<u8>,
+    /// The encoding, if the token was recognized.
+    pub encoding: Option<Encoding>,
+    /// Unix time in seconds the token was issued at, if it carries one.
+    pub issued_at: Option<u64>,
+    /// Unix time in seconds the first token in a chain of renewals was
+    /// issued at, if it differs from `issued_at`.
+    pub origin: Option<u64>,
+    /// The purpose the token was issued for, if it carries one.
+    pub purpose: Option<String>,
+    /// The cookie scope the token was issued under, if it carries one: empty
+    /// for host-only, or the cookie domain.
+    pub scope: Option<String>,
+    /// The audience the token was minted for, if it carries one.
+    pub audience: Option<String>,
+    /// How many times the token may be used, if more than once.
+    pub max_uses: Option<u8>,
+    /// The deployment environment the token was issued in, if it carries
+    /// one.
+    pub environment: Option<String>,
+    /// Why the token could not be parsed, if it could not be.
+    pub problem: Option<&'static str>,
+}
+
+impl RequestToken {
+    /// Describe an encoded token without verifying it.
+    pub fn inspect(value: &str) -> TokenInfo {
+        let mut info = TokenInfo {
+            len: value.len(),
+            version: None,
+            encoding: None,
+            issued_at: None,
+            origin: None,
+            purpose: None,
+            scope: None,
+            audience: None,
+            max_uses: None,
+            environment: None,
+            problem: None,
+        };
+        let (fixed, encoded_claims) = match value.find(claims::SEPARATOR) {
+            Some(i) => (&value[..i], Some(&value[i + 1..])),
+            None => (value, None),
+        };
+        let expected = Base64Url.encoded_len(TOKEN_LEN) * 2;
+        if fixed.len() != expected {
+            info.problem = Some("unexpected length");
+        } else if !value
+            .bytes()
+            .all(|b| is_base64url(b) || b == claims::SEPARATOR as u8)
+        {
+            info.problem = Some("unexpected characters");
+        } else {
+            match RequestToken::decode_with(value, &Base64Url) {
+                Ok(t) => {
+                    info.encoding = Some(Encoding::Base64Url);
+                    info.version = Some(version(&t.claims));
+                    info.issued_at = t.claims.issued_at;
+                    info.origin = t.claims.origin;
+                    info.purpose = t.claims.purpose;
+                    info.scope = t.claims.scope;
+                    info.audience = t.claims.audience;
+                    info.max_uses = t.claims.max_uses;
+                    info.environment = t.claims.environment;
+                }
+                Err(_)
+                    if encoded_claims.is_some()
+                        && RequestToken::decode_with(fixed, &Base64Url).is_ok() =>
+                {
+                    info.problem = Some("invalid claims");
+                }
+                Err(_) => info.problem = Some("invalid base64"),
+            }
+        }
+        info
+    }
+}
+
+fn version(claims: &Claims) -> u8 {
+    if claims.is_empty() {
+        PLAIN_FORMAT_VERSION
+    } else {
+        CLAIMS_FORMAT_VERSION
+    }
+}
+
+fn is_base64url(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'='
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoding, CLAIMS_FORMAT_VERSION, PLAIN_FORMAT_VERSION};
+    use crate::{CookieToken, RequestToken, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn inspect_valid() {
+        let s = CookieToken::new().gen_req_token().to_string();
+        let info = RequestToken::inspect(&s);
+        assert_eq!(info.version, Some(PLAIN_FORMAT_VERSION));
+        assert_eq!(info.encoding, Some(Encoding::Base64Url));
+        assert_eq!(info.issued_at, None);
+        assert_eq!(info.problem, None);
+    }
+
+    #[test]
+    fn inspect_claims() {
+        let config = XsrfConfig::new().with_ttl(Duration::from_secs(60));
+        let s = CookieToken::new().gen_req_token_with(&config).to_string();
+        let info = RequestToken::inspect(&s);
+        assert_eq!(info.version, Some(CLAIMS_FORMAT_VERSION));
+        assert!(info.issued_at.is_some());
+        let info = RequestToken::inspect(&format!("{}AAAA", s));
+        assert_eq!(info.problem, Some("invalid claims"));
+    }
+
+    #[test]
+    fn inspect_invalid() {
+        assert_eq!(
+            RequestToken::inspect("short").problem,
+            Some("unexpected length")
+        );
+        let info = RequestToken::inspect(&"!".repeat(88));
+        assert_eq!(info.problem, Some("unexpected characters"));
+        assert_eq!(info.version, None);
+    }
+}
@@ -0,0 +1,10 @@
+//! Interoperability with the token formats of other frameworks.
+//!
+//! These let a Rust service accept tokens issued by an application it is
+//! gradually replacing, while both sit behind the same frontend. They are
+//! meant for migrations, and are not as strong as the native format.
+mod csurf;
+mod spring;
+
+pub use csurf::{gen_csurf_token, verify_csurf_token};
+pub use spring::{verify_spring_token, SPRING_COOKIE_NAME, SPRING_HEADER_NAME};
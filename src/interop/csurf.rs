@@ -0,0 +1,69 @@
+//! Tokens of the Node `csurf` middleware.
+//!
+//! `csurf` stores a secret in a cookie or the session, and issues tokens of
+//! the form `<salt>-<hash>`, where the hash is the unpadded URL safe base64
+//! SHA-1 of `<salt>-<secret>`.
+use crate::sha1::sha1;
+use crate::{Error, Result};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use subtle::ConstantTimeEq;
+
+const SALT_LEN: usize = 8;
+
+fn tokenize(secret: &str, salt: &str) -> String {
+    let digest = sha1(format!("{}-{}", salt, secret).as_bytes());
+    format!(
+        "{}-{}",
+        salt,
+        base64::encode_config(digest, base64::URL_SAFE_NO_PAD)
+    )
+}
+
+/// Issue a token for `secret` in the `csurf` format, as `csurf` would.
+pub fn gen_csurf_token(secret: &str) -> String {
+    let salt: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SALT_LEN)
+        .collect();
+    tokenize(secret, &salt)
+}
+
+/// Verify a token issued by `csurf` for `secret`.
+pub fn verify_csurf_token(secret: &str, token: &str) -> Result<()> {
+    let salt = match token.find('-') {
+        Some(i) => &token[..i],
+        None => return Err(Error::InvalidToken),
+    };
+    let eq: bool = tokenize(secret, salt)
+        .as_bytes()
+        .ct_eq(token.as_bytes())
+        .into();
+    if eq {
+        Ok(())
+    } else {
+        Err(Error::TokenMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gen_csurf_token, verify_csurf_token};
+    use crate::Error;
+
+    const SECRET: &str = "Q1mJ4Hd0m6aC3XJ1nI2lV8fA";
+
+    #[test]
+    fn csurf_vector() {
+        verify_csurf_token(SECRET, "aB3dE5gH-AxpzOWhDRdxBFybshzeCgy3wyBU").unwrap();
+        assert_eq!(
+            verify_csurf_token(SECRET, "aB3dE5gH-AxpzOWhDRdxBFybshzeCgy3wyBV"),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(
+            verify_csurf_token(SECRET, "nodash"),
+            Err(Error::InvalidToken)
+        );
+        verify_csurf_token(SECRET, &gen_csurf_token(SECRET)).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+//! Tokens of Spring Security's `CookieCsrfTokenRepository`.
+//!
+//! Spring stores the token in the `XSRF-TOKEN` cookie, readable by scripts,
+//! and expects it back in the `X-XSRF-TOKEN` header or `_csrf` parameter.
+//! The submitted value is either the token itself, or since Spring Security
+//! 6 a masked form: URL safe base64 of a random pad followed by the token
+//! xor-ed with it.
+use crate::{Error, Result};
+use subtle::ConstantTimeEq;
+
+/// The name of the cookie Spring stores the token in.
+pub const SPRING_COOKIE_NAME: &str = "XSRF-TOKEN";
+/// The name of the header Spring expects the token in.
+pub const SPRING_HEADER_NAME: &str = "X-XSRF-TOKEN";
+
+/// Verify a token submitted to a Spring application against the value of
+/// its `XSRF-TOKEN` cookie, accepting both the plain and masked forms.
+pub fn verify_spring_token(cookie: &str, submitted: &str) -> Result<()> {
+    if cookie.is_empty() {
+        return Err(Error::InvalidToken);
+    }
+    let expected = cookie.as_bytes();
+    let unmasked = base64::decode_config(submitted.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .ok()
+        .filter(|b| b.len() == expected.len() * 2)
+        .map(|b| {
+            let (pad, mask) = b.split_at(expected.len());
+            pad.iter()
+                .zip(mask)
+                .map(|(p, m)| p ^ m)
+                .collect::<Vec<u8>>()
+        });
+    let plain: bool = expected.ct_eq(submitted.as_bytes()).into();
+    let masked: bool = unmasked.is_some_and(|u| expected.ct_eq(&u).into());
+    if plain || masked {
+        Ok(())
+    } else {
+        Err(Error::TokenMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_spring_token;
+    use crate::Error;
+
+    const TOKEN: &str = "4b1c8a3e-7d2f-4e9a-b5c6-0f1e2d3c4b5a";
+
+    #[test]
+    fn spring_plain_and_masked() {
+        verify_spring_token(TOKEN, TOKEN).unwrap();
+        let pad = [0x5au8; 36];
+        let mut raw = pad.to_vec();
+        raw.extend(TOKEN.bytes().zip(pad.iter()).map(|(t, p)| t ^ p));
+        let masked = base64::encode_config(&raw, base64::URL_SAFE);
+        verify_spring_token(TOKEN, &masked).unwrap();
+        assert_eq!(
+            verify_spring_token(TOKEN, "4b1c8a3e"),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(verify_spring_token("", ""), Err(Error::InvalidToken));
+    }
+}
@@ -0,0 +1,73 @@
+//! Writing JSON by hand, for the few small documents the crate produces.
+
+/// Append `s` as a quoted JSON string.
+pub(crate) fn push_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // escaping < and > keeps the output safe to inline in HTML
+            c if (c as u32) < 0x20 || c == '<' || c == '>' || c == '&' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Builds a JSON object with string members.
+#[derive(Default)]
+pub(crate) struct Object {
+    out: String,
+}
+
+impl Object {
+    pub(crate) fn new() -> Object {
+        Self::default()
+    }
+
+    pub(crate) fn str(mut self, key: &str, value: &str) -> Object {
+        self.key(key);
+        push_str(&mut self.out, value);
+        self
+    }
+
+    pub(crate) fn raw(mut self, key: &str, value: &str) -> Object {
+        self.key(key);
+        self.out.push_str(value);
+        self
+    }
+
+    fn key(&mut self, key: &str) {
+        self.out.push(if self.out.is_empty() { '{' } else { ',' });
+        push_str(&mut self.out, key);
+        self.out.push(':');
+    }
+
+    pub(crate) fn finish(mut self) -> String {
+        if self.out.is_empty() {
+            self.out.push('{');
+        }
+        self.out.push('}');
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Object;
+
+    #[test]
+    fn writes_objects() {
+        assert_eq!(Object::new().finish(), "{}");
+        assert_eq!(
+            Object::new().str("a", "x\"<\n").raw("b", "1").finish(),
+            r#"{"a":"x\"\u003c\n","b":1}"#
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Secret keys for the keyed strategies.
+use rand::{thread_rng, Rng};
+use std::fmt;
+
+/// The recommended minimum key length, in bytes.
+pub const MIN_KEY_LEN: usize = 32;
+
+/// A secret key. Its `Debug` output is redacted.
+///
+/// With the `mlock` feature on unix, the key bytes are locked in memory so
+/// they are never written to swap. This is best effort: if the lock limit is
+/// reached the key is still usable, just not locked. Memory is locked in
+/// whole pages, which keys share with each other and with other heap data,
+/// so a page stays locked until the last key on it is dropped.
+pub struct Key {
+    bytes: Vec<u8>,
+}
+
+impl Key {
+    /// Use the given bytes as a key. They should be at least `MIN_KEY_LEN`
+    /// bytes of high entropy data.
+    pub fn new(bytes: &[u8]) -> Key {
+        let key = Self::zeroed(bytes.len());
+        key.with_mut(|b| b.copy_from_slice(bytes))
+    }
+
+    /// Generate a random key of `MIN_KEY_LEN` bytes.
+    pub fn generate() -> Key {
+        Self::zeroed(MIN_KEY_LEN).with_mut(|b| thread_rng().fill(b))
+    }
+
+    /// Allocate and lock the storage before any secret is written to it.
+    fn zeroed(len: usize) -> Key {
+        let bytes = vec![0; len];
+        lock(&bytes);
+        Self { bytes }
+    }
+
+    fn with_mut<F: FnOnce(&mut [u8])>(mut self, f: F) -> Key {
+        f(&mut self.bytes);
+        self
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+impl Clone for Key {
+    fn clone(&self) -> Key {
+        Key::new(&self.bytes)
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        zeroize(&mut self.bytes);
+        unlock(&self.bytes);
+    }
+}
+
+/// Overwrite secret bytes with zeros. The writes are volatile, since plain
+/// writes to memory about to be freed are dead stores the optimizer may
+/// remove.
+pub(crate) fn zeroize(bytes: &mut [u8]) {
+    for b in bytes.iter_mut() {
+        // SAFETY: `b` is a valid, aligned and exclusive reference.
+        unsafe { std::ptr::write_volatile(b, 0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// The number of live keys on each locked page, by page address. Locks
+/// don't nest, so a page is only unlocked once no key uses it.
+#[cfg(all(feature = "mlock", unix))]
+static LOCKED_PAGES: std::sync::Mutex<std::collections::BTreeMap<usize, usize>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+/// The addresses of the pages spanned by `bytes`, and the page size.
+#[cfg(all(feature = "mlock", unix))]
+fn pages(bytes: &[u8]) -> (impl Iterator<Item = usize>, usize) {
+    // SAFETY: sysconf has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+    let start = bytes.as_ptr() as usize / page;
+    let end = (bytes.as_ptr() as usize + bytes.len() - 1) / page;
+    ((start..=end).map(move |p| p * page), page)
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn lock(bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    let (pages, page_len) = pages(bytes);
+    for addr in pages {
+        let count = locked.entry(addr).or_insert(0);
+        if *count == 0 {
+            // SAFETY: the page holds a live allocation owned by the key,
+            // which is never reallocated. Failure only means it stays
+            // swappable.
+            unsafe { libc::mlock(addr as *const libc::c_void, page_len) };
+        }
+        *count += 1;
+    }
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn unlock(bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    let (pages, page_len) = pages(bytes);
+    for addr in pages {
+        let count = locked.entry(addr).or_insert(1);
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&addr);
+            // SAFETY: as for lock, and no other key uses the page.
+            unsafe { libc::munlock(addr as *const libc::c_void, page_len) };
+        }
+    }
+}
+
+#[cfg(not(all(feature = "mlock", unix)))]
+fn lock(_: &[u8]) {}
+
+#[cfg(not(all(feature = "mlock", unix)))]
+fn unlock(_: &[u8]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::Key;
+
+    #[test]
+    fn key_round_trip() {
+        let key = Key::new(&[7; 40]);
+        assert_eq!(key.clone().as_bytes(), &[7; 40][..]);
+        assert_eq!(Key::generate().as_bytes().len(), super::MIN_KEY_LEN);
+        assert_eq!(format!("{:?}", key), "Key(..)");
+    }
+
+    #[cfg(all(feature = "mlock", unix))]
+    #[test]
+    fn shared_pages_stay_locked() {
+        let mut keys: Vec<Key> = (0..8).map(|_| Key::generate()).collect();
+        let first = keys.remove(0);
+        let (pages, _) = super::pages(first.as_bytes());
+        let pages: Vec<usize> = pages.collect();
+        drop(keys);
+        let locked = super::LOCKED_PAGES.lock().unwrap();
+        assert!(pages.iter().all(|p| locked.get(p).is_some_and(|&n| n > 0)));
+    }
+}
@@ -0,0 +1,169 @@
+//! Accepting tokens of older deployments during a migration.
+use crate::claims::SEPARATOR;
+use crate::codec::decode_vec;
+use crate::{
+    Base64Url, Clock, CookieToken, Error, RequestToken, Result, SystemClock, TokenCodec, TOKEN_LEN,
+};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+/// The shortest cookie token accepted, below which guessing it is feasible.
+const MIN_LEN: usize = 16;
+
+/// Verifies plain tokens of several sizes, so a deployment changing its
+/// token size does not invalidate every session at once.
+///
+/// Tokens of the native size are verified as usual, and may carry claims.
+/// Tokens of other accepted sizes use the same one-time-pad scheme, and
+/// cannot carry claims.
+#[derive(Clone, Debug)]
+pub struct SizeMigration {
+    lengths: Vec<usize>,
+}
+
+impl SizeMigration {
+    /// Accept cookie tokens of the given lengths in bytes.
+    ///
+    /// Panics if a length is shorter than 16 bytes. An empty cookie token
+    /// would accept an empty request token, and short ones can be guessed.
+    pub fn new(lengths: &[usize]) -> SizeMigration {
+        assert!(
+            lengths.iter().all(|&len| len >= MIN_LEN),
+            "xsrf cookie token length too short"
+        );
+        Self {
+            lengths: lengths.to_vec(),
+        }
+    }
+
+    /// Verify encoded tokens, in the default encoding.
+    pub fn verify(&self, cookie: &str, token: &str) -> Result<()> {
+        let cookie = decode_vec(&Base64Url, cookie)?;
+        if !self.lengths.contains(&cookie.len()) {
+            return Err(Error::InvalidToken);
+        }
+        if cookie.len() == TOKEN_LEN {
+            let mut data = [0; TOKEN_LEN];
+            data.copy_from_slice(&cookie);
+            return CookieToken::from_bytes(data).verify_req_token(RequestToken::try_from(token)?);
+        }
+        let half = Base64Url.encoded_len(cookie.len());
+        if token.len() != half * 2 || !token.is_char_boundary(half) {
+            return Err(Error::InvalidToken);
+        }
+        let mut otp = vec![0; cookie.len()];
+        let mut mask = vec![0; cookie.len()];
+        Base64Url.decode(&token[..half], &mut otp)?;
+        Base64Url.decode(&token[half..], &mut mask)?;
+        let expected: Vec<u8> = otp.iter().zip(&mask).map(|(o, m)| o ^ m).collect();
+        if bool::from(expected.ct_eq(&cookie)) {
+            Ok(())
+        } else {
+            Err(Error::TokenMismatch)
+        }
+    }
+}
+
+/// Parses request tokens while phasing out the bare format.
+///
+/// Tokens carrying claims use the versioned format, while plain tokens, such
+/// as those from `gen_req_token`, are the bare 88 character base64 of older
+/// releases. Once every token is issued with claims, a `LegacyCompat` keeps
+/// accepting bare tokens until a deadline, and counts them so operators can
+/// tell when no clients hold them anymore.
+#[derive(Clone, Debug)]
+pub struct LegacyCompat {
+    accept_until: u64,
+    clock: Arc<dyn Clock>,
+    seen: Arc<AtomicU64>,
+}
+
+impl LegacyCompat {
+    /// Accept bare tokens until `accept_until`, in seconds since the unix
+    /// epoch.
+    pub fn new(accept_until: u64) -> LegacyCompat {
+        Self {
+            accept_until,
+            clock: Arc::new(SystemClock),
+            seen: Arc::default(),
+        }
+    }
+
+    /// Use the given clock instead of the system clock.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> LegacyCompat {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Parse an encoded request token. Bare tokens are counted even once
+    /// they are no longer accepted.
+    pub fn parse(&self, value: &str) -> Result<RequestToken> {
+        if !value.contains(SEPARATOR) {
+            self.seen.fetch_add(1, Ordering::Relaxed);
+            if self.clock.now() > self.accept_until {
+                return Err(Error::InvalidToken);
+            }
+        }
+        RequestToken::try_from(value)
+    }
+
+    /// How many bare tokens were seen.
+    pub fn legacy_count(&self) -> u64 {
+        self.seen.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LegacyCompat, SizeMigration};
+    use crate::{CookieToken, Error, TestClock, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn accepts_configured_sizes() {
+        let m = SizeMigration::new(&[16, 32]);
+        let ct = CookieToken::new();
+        m.verify(&ct.to_string(), &ct.gen_req_token().to_string())
+            .unwrap();
+
+        let legacy = [9u8; 16];
+        let otp = [3u8; 16];
+        let mask: Vec<u8> = otp.iter().zip(&legacy).map(|(o, c)| o ^ c).collect();
+        let enc = |b: &[u8]| base64::encode_config(b, base64::URL_SAFE);
+        let token = format!("{}{}", enc(&otp), enc(&mask));
+        m.verify(&enc(&legacy), &token).unwrap();
+        assert_eq!(
+            m.verify(&enc(&[8u8; 16]), &token),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(
+            SizeMigration::new(&[32]).verify(&enc(&legacy), &token),
+            Err(Error::InvalidToken)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_short_sizes() {
+        SizeMigration::new(&[0, 32]);
+    }
+
+    #[test]
+    fn legacy_compat_window() {
+        let clock = TestClock::new(1_000_000);
+        let compat = LegacyCompat::new(1_000_100).with_clock(clock.clone());
+        let config = XsrfConfig::new().with_ttl(Duration::from_secs(60));
+        let ct = CookieToken::new();
+        let versioned = ct.gen_req_token_with(&config).to_string();
+        let bare = ct.gen_req_token().to_string();
+        compat.parse(&versioned).unwrap();
+        assert_eq!(compat.legacy_count(), 0);
+        compat.parse(&bare).unwrap();
+        clock.advance(Duration::from_secs(101));
+        assert_eq!(compat.parse(&bare).err(), Some(Error::InvalidToken));
+        compat.parse(&versioned).unwrap();
+        assert_eq!(compat.legacy_count(), 2);
+    }
+}
@@ -30,33 +30,305 @@
 //! - [`rand`](https://docs.rs/rand) is used to generate cryptographically
 //!   secure tokens.
 //! - `RequestToken`s use a one-time-pad and are xor-ed with the `CookieToken`
-//!    to protect against [BREACH](http://breachattack.com/).
+//!   to protect against [BREACH](http://breachattack.com/).
 //! - [`subtle`](https://docs.rs/subtle) is used to protect against timing
 //!   attacks.
+//! - Tokens are encoded using URL safe base64 by default. A different
+//!   [`TokenCodec`] can be used via the `_with` variants of the encoding
+//!   functions, and `decode_padding_optional` also accepts tokens whose
+//!   padding was stripped by a client.
+//! - Binary formats can skip the textual encoding entirely and store the raw
+//!   bytes via `as_bytes`/`to_bytes` and `from_bytes`.
+//! - `to_stack_string` encodes tokens into a fixed size `StackString`, for
+//!   allocation averse environments, and `gen_req_token_string` returns a
+//!   new request token along with its encoding.
+//! - `verify_req_token_any` verifies against several cookie tokens, for
+//!   sessions legitimately holding more than one.
+//! - Backends using the synchronizer pattern can store only the
+//!   `Fingerprint` of the `CookieToken`, and use
+//!   `verify_req_token_against_fingerprint`.
+//! - Request tokens can optionally carry claims, such as the time they were
+//!   issued at, which are bound to the `CookieToken` using HMAC-SHA-256. An
+//!   `XsrfConfig` controls which claims are issued and enforced, via
+//!   `gen_req_token_with` and `verify_req_token_with`. Time is read from a
+//!   `Clock`, which can be replaced by a `TestClock` in tests.
+//! - Request tokens can also carry a purpose, such as `"password-change"`,
+//!   allowing one endpoint to safely serve multiple distinct forms. Marker
+//!   types implementing `Purpose` make the purpose part of the type, as a
+//!   `PurposeToken`.
+//! - `XsrfConfig::with_scope` embeds the `CookieScope` of the token cookie,
+//!   host-only or domain-wide, and rejects tokens issued under another.
+//! - `SsoProtection` mints tokens on one domain of a single sign-on
+//!   deployment that verify on another, using a shared `Key` and an audience.
+//! - `ActionLinks` signs expiring, single purpose tokens for links sent
+//!   out-of-band, such as email confirmations, with a `Key`.
+//! - `gen_req_token_windowed` issues tokens that rotate with fixed time
+//!   windows, like TOTP codes, expiring without embedding a timestamp.
+//! - Request tokens can be made one-time by configuring a `ReplayStore`, or
+//!   by verifying them with an `AsyncReplayStore`.
+//! - `XsrfConfig::with_max_uses` instead gives tokens a use budget, counted
+//!   by the replay store, for flows that legitimately resubmit.
+//! - Cookie tokens can be issued in a `TokenFamily`, such as one per user,
+//!   and a `RevocationStore` rejects every token of a revoked family.
+//! - A `CookieLifetime` gives cookie tokens an absolute timeout and an idle
+//!   timeout extended on each verification, like a typical session policy.
+//! - `OneTime` wraps a request token so verifying it consumes it, and
+//!   records it in the replay store.
+//! - An `OtpPool` pre-generates one-time-pads on a background thread, for
+//!   servers issuing many tokens per second. More generally,
+//!   `XsrfConfig::with_entropy` chooses the `Entropy` source of pads.
+//! - A `Binding` ties request tokens to properties of the connection, such
+//!   as the TLS channel, a client fingerprint or the client network, so
+//!   tokens replayed from elsewhere fail.
+//! - `SignedDoubleSubmit` implements the OWASP signed double submit cookie
+//!   pattern, binding request tokens to the session using a secret `Key`.
+//! - A `Secret` loads one master secret from the environment or a file, and
+//!   derives the individual `Key`s from it using HKDF. `XsrfConfig::stateless`
+//!   is a preset for serverless deployments using them. With the `mlock`
+//!   feature, key bytes are locked in memory so they are never swapped.
+//! - `TokenCookie` describes the cookie carrying the `CookieToken`, and
+//!   `check_same_site` flags dangerous `SameSite` configurations. For users
+//!   without a cookie library, `CookieToken::from_cookie_header` parses the
+//!   raw `Cookie` header. Values too large for one cookie can be chunked
+//!   with `set_cookie_headers` and reassembled with `chunked_value`.
+//! - A `Convention` bundles the cookie, header and field names of common
+//!   stacks, such as Django's or Angular's, for interop.
+//! - `XsrfConfig::validate` checks the config, cookie, key and extractor for
+//!   dangerous combinations before the server starts taking traffic. Given
+//!   the app's `CorsPolicy`, it also flags CORS settings letting other sites
+//!   read tokens.
+//! - Session integrations implement `Session` to store the `CookieToken` in
+//!   the server side session instead of a cookie, with pre-session tokens
+//!   for login forms that are rotated on login.
+//! - `CookieToken::migrate` replaces the anonymous token on login with one
+//!   in the user's `TokenFamily`, while accepting its request tokens issued
+//!   before login for a short grace window. `CookieToken::rotate` does the
+//!   same until an explicit cutoff.
+//! - The `CsrfProtection` trait abstracts over the strategies above, so
+//!   integrations can be written once and the strategy chosen by the user.
+//! - `CsrfProtection::verified` returns a `Verified<RequestToken>`, which
+//!   code performing state changing actions can require as proof. The
+//!   request level helpers, such as `Extractor::verify_any` and
+//!   `CookieLifetime::verify`, return one too, and `refresh_header` requires
+//!   one.
+//! - Integrations implement the `Request` trait for their framework's request
+//!   type, and consult a `Policy` to decide which requests need a token.
+//!   Requests from other services, authenticated by bearer tokens or
+//!   service certificates, can't be forged cross-site and can be exempted.
+//!   A `ContentTypeAllowlist` rejects state changing requests browsers could
+//!   send cross-site without a preflight, alongside or instead of tokens.
+//! - `PageSecrets` generates a page's request token and its CSP script
+//!   nonce together, from the same source of randomness.
+//! - A `TokenScope` caches the request token for the duration of a request,
+//!   so rendering many forms does not generate many tokens, and only issues
+//!   a `CookieToken` once a token is actually needed.
+//! - An `Extractor` finds the request token in headers, urlencoded and JSON
+//!   bodies, and with the `multipart` feature, `multipart/form-data` bodies.
+//!   `Extractor::verify_any` accepts a request if any of the token candidates
+//!   it carries verifies, reporting which one did.
+//! - An `AuditSink` configured on an `XsrfConfig` receives an `AuditEvent`
+//!   for every token issued, verified, rejected, rotated or revoked. Events
+//!   identify tokens by the `AuditId` of their family, a hash of it.
+//! - `verify_req_token_outcome` reports exactly why verification failed as a
+//!   `VerifyOutcome`, for operators' logs.
+//! - Verification failures map to a `Rejection`, describing the response to
+//!   send using the status, headers and body shared by all integrations. A
+//!   `Problem` builds RFC 7807 `application/problem+json` rejections, and a
+//!   `RejectionHandler` allows users to customize the response entirely.
+//!   Rejections can optionally carry fresh tokens, allowing clients to retry.
+//! - `refresh` implements an endpoint handing out fresh request tokens for
+//!   AJAX use, and `refresh_header` hands out the next one in a response
+//!   header after every successful unsafe request.
+//! - `SizeMigration` accepts tokens of older sizes while a deployment changes
+//!   its token size, and `LegacyCompat` phases out bare tokens without
+//!   claims, counting those still seen.
+//! - Tokens issued by Node's `csurf` and Spring Security's cookie repository
+//!   can be verified with `verify_csurf_token` and `verify_spring_token`,
+//!   for services migrating away from, or running beside, those apps.
+//! - The `xsrf-cli` binary, behind the `cli` feature, generates, verifies and
+//!   inspects tokens from the shell.
+//! - With the `test-client` feature, a `TestClient` drives an app through
+//!   CSRF round trips, scraping tokens from its pages, for end-to-end tests.
+//! - `test_vectors` and `test_vectors_json` provide known answer vectors for
+//!   ports of the token scheme to other languages.
+//! - `self_test` checks the entropy source of an `XsrfConfig` and token
+//!   round trips, and is meant to be run at startup.
+//! - `RequestToken::inspect` describes an encoded token without verifying
+//!   it, which is useful to explain why a token was rejected.
+use claims::Claims;
 use rand::{thread_rng, Rng};
+use std::fmt;
+use std::sync::OnceLock;
 use subtle::ConstantTimeEq;
 
+mod action;
+mod any;
+mod audit;
+mod binding;
+mod claims;
+mod clock;
+mod codec;
+mod config;
+mod content_type;
+mod convention;
+mod cookie;
+mod cors;
+mod entropy;
+mod extract;
+mod family;
+mod fingerprint;
+mod health;
+mod inspect;
+mod interop;
+mod json;
+mod key;
+mod legacy;
+mod lifetime;
+mod migrate;
+mod one_time;
+mod outcome;
+mod page;
+mod percent;
+mod policy;
+mod pool;
+mod protection;
+mod purpose;
+mod refresh;
+mod rejection;
+mod replay;
+mod request;
+mod scope;
+mod secret;
+mod session;
+mod sha1;
+mod sha256;
+mod signed;
+mod sso;
+mod stack;
+#[cfg(feature = "test-client")]
+mod test_client;
+mod validate;
+mod vectors;
+mod verified;
+mod window;
+
+pub use action::ActionLinks;
+pub use any::{verify_req_token_any, verify_req_token_any_with};
+pub use audit::{AuditEvent, AuditId, AuditSink, MemoryAuditSink, AUDIT_ID_BYTES};
+pub use binding::{client_fingerprint, Binding, Bound, IpTolerance};
+pub use clock::{Clock, SystemClock, TestClock};
+pub use codec::{Base64Url, Base64UrlNoPad, TokenCodec};
+pub use config::XsrfConfig;
+pub use content_type::ContentTypeAllowlist;
+pub use convention::Convention;
+pub use cookie::{check_same_site, CookieError, CookieScope, SameSite, TokenCookie};
+pub use cors::CorsPolicy;
+pub use entropy::Entropy;
+#[cfg(feature = "multipart")]
+pub use extract::multipart_field;
+pub use extract::{Extractor, Source};
+pub use family::{MemoryRevocationStore, RevocationStore, TokenFamily, TOKEN_FAMILY_BYTES};
+pub use fingerprint::{verify_req_token_against_fingerprint, Fingerprint};
+pub use health::{self_test, SelfTestError};
+pub use inspect::{Encoding, TokenInfo, CLAIMS_FORMAT_VERSION, PLAIN_FORMAT_VERSION};
+pub use interop::{
+    gen_csurf_token, verify_csurf_token, verify_spring_token, SPRING_COOKIE_NAME,
+    SPRING_HEADER_NAME,
+};
+pub use key::{Key, MIN_KEY_LEN};
+pub use legacy::{LegacyCompat, SizeMigration};
+pub use lifetime::CookieLifetime;
+pub use migrate::{Migration, TransitionVerifier};
+pub use one_time::OneTime;
+pub use outcome::VerifyOutcome;
+pub use page::PageSecrets;
+pub use policy::Policy;
+pub use pool::OtpPool;
+pub use protection::{CsrfProtection, Plain};
+pub use purpose::{Purpose, PurposeToken};
+pub use refresh::{refresh, refresh_header, Refresh};
+pub use rejection::{
+    DefaultRejectionHandler, Problem, Rejection, RejectionHandler, STATUS_EXPIRED,
+};
+pub use replay::{
+    AsyncReplayStore, BloomReplayStore, BoxFuture, MemoryReplayStore, Nonce, ReplayStore,
+    ShardedReplayStore,
+};
+pub use request::Request;
+pub use scope::TokenScope;
+pub use secret::{Secret, SecretError};
+pub use session::{Session, SESSION_KEY};
+pub use signed::SignedDoubleSubmit;
+pub use sso::SsoProtection;
+pub use stack::{StackString, COOKIE_TOKEN_ENCODED_LEN, REQUEST_TOKEN_ENCODED_LEN};
+#[cfg(feature = "test-client")]
+pub use test_client::{scrape_token, ClientRequest, ClientResponse, TestClient};
+pub use validate::{ConfigError, Validation};
+pub use vectors::{test_vectors, test_vectors_json, TestVector};
+pub use verified::Verified;
+
 const TOKEN_LEN: usize = 32;
-const ENCODED_LEN: usize = 44;
-static BC: base64::Config = base64::URL_SAFE;
 
-#[derive(thiserror::Error, Debug)]
+/// Length of the raw bytes of a `CookieToken`.
+pub const COOKIE_TOKEN_BYTES: usize = TOKEN_LEN;
+/// Length of the raw bytes of a `RequestToken`.
+pub const REQUEST_TOKEN_BYTES: usize = TOKEN_LEN * 2;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     #[error("invalid xsrf token")]
     InvalidToken,
     #[error("xsrf token mismatch")]
     TokenMismatch,
+    #[error("xsrf token expired")]
+    Expired,
+    #[error("xsrf token purpose mismatch")]
+    WrongPurpose,
+    #[error("xsrf token already used")]
+    Replayed,
+    #[error("xsrf token scope mismatch")]
+    WrongScope,
+    #[error("xsrf token environment mismatch")]
+    WrongEnvironment,
+    #[error("xsrf token revoked")]
+    Revoked,
+    #[error("xsrf honeypot field filled")]
+    Honeypot,
+    #[error("xsrf content type not allowed")]
+    ContentType,
+}
+
+impl Error {
+    /// A stable machine readable code for the error, for clients and log
+    /// pipelines to branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidToken => "xsrf.malformed",
+            Error::TokenMismatch => "xsrf.mismatch",
+            Error::Expired => "xsrf.expired",
+            Error::WrongPurpose => "xsrf.wrong_purpose",
+            Error::Replayed => "xsrf.replayed",
+            Error::WrongScope => "xsrf.wrong_scope",
+            Error::WrongEnvironment => "xsrf.wrong_environment",
+            Error::Revoked => "xsrf.revoked",
+            Error::Honeypot => "xsrf.honeypot",
+            Error::ContentType => "xsrf.content_type",
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub struct CookieToken {
     data: [u8; TOKEN_LEN],
+    encoded: OnceLock<StackString<COOKIE_TOKEN_ENCODED_LEN>>,
+    /// The family of the token this one was derived from, if any.
+    family: Option<TokenFamily>,
 }
 
-impl ToString for CookieToken {
-    fn to_string(&self) -> String {
-        base64::encode_config(&self.data, BC)
+impl fmt::Display for CookieToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode_with(&Base64Url))
     }
 }
 
@@ -64,62 +336,121 @@ impl std::convert::TryFrom<&str> for CookieToken {
     type Error = Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-        if value.len() != ENCODED_LEN {
-            return Err(Error::InvalidToken);
-        }
-        let mut t = Self {
-            data: [0; TOKEN_LEN],
-        };
-        if base64::decode_config_slice(value, BC, &mut t.data).is_err() {
-            return Err(Error::InvalidToken);
-        }
-        Ok(t)
+        Self::decode_with(value, &Base64Url)
+    }
+}
+
+impl Default for CookieToken {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl CookieToken {
     pub fn new() -> CookieToken {
-        let mut t = Self {
-            data: [0; TOKEN_LEN],
-        };
-        thread_rng().fill(&mut t.data);
-        t
+        let mut data = [0; TOKEN_LEN];
+        thread_rng().fill(&mut data);
+        Self::from_bytes(data)
+    }
+
+    /// Construct a token from its raw bytes.
+    pub const fn from_bytes(data: [u8; COOKIE_TOKEN_BYTES]) -> CookieToken {
+        Self {
+            data,
+            encoded: OnceLock::new(),
+            family: None,
+        }
+    }
+
+    /// The raw bytes of the token.
+    pub const fn as_bytes(&self) -> &[u8; COOKIE_TOKEN_BYTES] {
+        &self.data
+    }
+
+    /// Encode the token using the given codec.
+    pub fn encode_with<C: TokenCodec + ?Sized>(&self, codec: &C) -> String {
+        let mut s = String::with_capacity(codec.encoded_len(TOKEN_LEN));
+        codec.encode(&self.data, &mut s);
+        s
+    }
+
+    /// The token in its default encoding, computed once and cached. Useful
+    /// for long lived tokens, such as those kept in session state, that are
+    /// written out on every response.
+    pub fn encoded(&self) -> &str {
+        self.encoded.get_or_init(|| self.to_stack_string())
+    }
+
+    /// Decode a token previously encoded with `encode_with` using the same
+    /// codec.
+    pub fn decode_with<C: TokenCodec + ?Sized>(value: &str, codec: &C) -> Result<Self> {
+        let mut data = [0; TOKEN_LEN];
+        codec.decode(value, &mut data)?;
+        Ok(Self::from_bytes(data))
     }
 
     pub fn gen_req_token(&self) -> RequestToken {
+        self.issue(Claims::default())
+    }
+
+    /// Verify the token is derived from this `CookieToken`. Any claims the
+    /// token carries are bound to it, but not enforced. Use
+    /// `verify_req_token_with` to enforce them.
+    pub fn verify_req_token(&self, token: RequestToken) -> Result<()> {
+        self.check(&token)
+    }
+
+    fn issue(&self, claims: Claims) -> RequestToken {
+        let mut otp = [0; TOKEN_LEN];
+        thread_rng().fill(&mut otp);
+        self.issue_with_otp(otp, claims)
+    }
+
+    fn issue_with_otp(&self, otp: [u8; TOKEN_LEN], claims: Claims) -> RequestToken {
         let mut t = RequestToken {
-            otp: [0; TOKEN_LEN],
+            otp,
             mask: [0; TOKEN_LEN],
+            claims,
         };
-        thread_rng().fill(&mut t.otp);
-        xor_into(&t.otp, &self.data, &mut t.mask);
+        let derived = self.derive(&t.claims);
+        xor_into(&t.otp, &derived, &mut t.mask);
         t
     }
 
-    pub fn verify_req_token(&self, token: RequestToken) -> Result<()> {
+    fn check(&self, token: &RequestToken) -> Result<()> {
         let mut expected = [0; TOKEN_LEN];
         xor_into(&token.otp, &token.mask, &mut expected);
-        let eq: bool = expected.ct_eq(&self.data).into();
+        let eq: bool = expected.ct_eq(&self.derive(&token.claims)).into();
         if eq {
             Ok(())
         } else {
             Err(Error::TokenMismatch)
         }
     }
+
+    /// The value the request token masks. This is the token itself for
+    /// tokens without claims, keeping the original format intact.
+    fn derive(&self, claims: &Claims) -> [u8; TOKEN_LEN] {
+        if claims.is_empty() {
+            self.data
+        } else {
+            sha256::hmac(&self.data, &[DERIVE_DOMAIN, &claims.encode()])
+        }
+    }
 }
 
+const DERIVE_DOMAIN: &[u8] = b"xsrf claims\0";
+
 #[derive(Clone)]
 pub struct RequestToken {
     otp: [u8; TOKEN_LEN],
     mask: [u8; TOKEN_LEN],
+    claims: Claims,
 }
 
-impl ToString for RequestToken {
-    fn to_string(&self) -> String {
-        let mut s = String::with_capacity(ENCODED_LEN * 2);
-        base64::encode_config_buf(self.otp, BC, &mut s);
-        base64::encode_config_buf(self.mask, BC, &mut s);
-        s
+impl fmt::Display for RequestToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode_with(&Base64Url))
     }
 }
 
@@ -127,19 +458,66 @@ impl std::convert::TryFrom<&str> for RequestToken {
     type Error = Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-        if value.len() != ENCODED_LEN * 2 {
-            return Err(Error::InvalidToken);
-        }
+        Self::decode_with(value, &Base64Url)
+    }
+}
+
+impl RequestToken {
+    /// Construct a token from its raw bytes, as returned by `to_bytes`.
+    pub const fn from_bytes(data: &[u8; REQUEST_TOKEN_BYTES]) -> RequestToken {
         let mut t = Self {
             otp: [0; TOKEN_LEN],
             mask: [0; TOKEN_LEN],
+            claims: Claims::EMPTY,
         };
-        if base64::decode_config_slice(&value[..ENCODED_LEN], BC, &mut t.otp).is_err() {
-            return Err(Error::InvalidToken);
+        let (otp, mask) = data.split_at(TOKEN_LEN);
+        t.otp.copy_from_slice(otp);
+        t.mask.copy_from_slice(mask);
+        t
+    }
+
+    /// The raw bytes of the token. Claims are not included, so tokens
+    /// carrying claims must use their encoded form instead.
+    pub fn to_bytes(&self) -> [u8; REQUEST_TOKEN_BYTES] {
+        let mut data = [0; REQUEST_TOKEN_BYTES];
+        data[..TOKEN_LEN].copy_from_slice(&self.otp);
+        data[TOKEN_LEN..].copy_from_slice(&self.mask);
+        data
+    }
+
+    /// Encode the token using the given codec.
+    pub fn encode_with<C: TokenCodec + ?Sized>(&self, codec: &C) -> String {
+        let mut s = String::with_capacity(codec.encoded_len(TOKEN_LEN) * 2);
+        codec.encode(&self.otp, &mut s);
+        codec.encode(&self.mask, &mut s);
+        if !self.claims.is_empty() {
+            s.push(claims::SEPARATOR);
+            codec.encode(&self.claims.encode(), &mut s);
         }
-        if base64::decode_config_slice(&value[ENCODED_LEN..], BC, &mut t.mask).is_err() {
+        s
+    }
+
+    /// Decode a token previously encoded with `encode_with` using the same
+    /// codec.
+    pub fn decode_with<C: TokenCodec + ?Sized>(value: &str, codec: &C) -> Result<Self> {
+        let (value, encoded_claims) = match value.find(claims::SEPARATOR) {
+            Some(i) => (&value[..i], Some(&value[i + 1..])),
+            None => (value, None),
+        };
+        let half = codec.encoded_len(TOKEN_LEN);
+        if value.len() != half * 2 || !value.is_char_boundary(half) {
             return Err(Error::InvalidToken);
         }
+        let mut t = Self {
+            otp: [0; TOKEN_LEN],
+            mask: [0; TOKEN_LEN],
+            claims: Claims::default(),
+        };
+        codec.decode(&value[..half], &mut t.otp)?;
+        codec.decode(&value[half..], &mut t.mask)?;
+        if let Some(encoded) = encoded_claims {
+            t.claims = Claims::decode(&codec::decode_vec(codec, encoded)?)?;
+        }
         Ok(t)
     }
 }
@@ -156,14 +534,38 @@ fn xor_into(a: &[u8], b: &[u8], into: &mut [u8]) {
 
 #[cfg(test)]
 mod tests {
-    use super::{CookieToken, RequestToken, ENCODED_LEN};
+    use super::{CookieToken, Error, RequestToken, Result, TokenCodec, COOKIE_TOKEN_ENCODED_LEN};
     use std::convert::TryInto;
 
+    struct Hex;
+
+    impl TokenCodec for Hex {
+        fn encoded_len(&self, len: usize) -> usize {
+            len * 2
+        }
+
+        fn encode(&self, data: &[u8], out: &mut String) {
+            data.iter()
+                .for_each(|b| out.push_str(&format!("{:02x}", b)));
+        }
+
+        fn decode(&self, input: &str, out: &mut [u8]) -> Result<()> {
+            if input.len() != self.encoded_len(out.len()) {
+                return Err(Error::InvalidToken);
+            }
+            for (i, b) in out.iter_mut().enumerate() {
+                *b = u8::from_str_radix(&input[i * 2..i * 2 + 2], 16)
+                    .map_err(|_| Error::InvalidToken)?;
+            }
+            Ok(())
+        }
+    }
+
     #[test]
     fn cookie_token_to_from_string() {
         let original = CookieToken::new();
         let s = original.to_string();
-        assert_eq!(s.len(), ENCODED_LEN);
+        assert_eq!(s.len(), COOKIE_TOKEN_ENCODED_LEN);
         let decoded: CookieToken = s.as_str().try_into().unwrap();
         assert_eq!(original.data, decoded.data);
     }
@@ -173,7 +575,7 @@ mod tests {
         let ct = CookieToken::new();
         let original = ct.gen_req_token();
         let s = original.to_string();
-        assert_eq!(s.len(), ENCODED_LEN * 2);
+        assert_eq!(s.len(), COOKIE_TOKEN_ENCODED_LEN * 2);
         let decoded: RequestToken = s.as_str().try_into().unwrap();
         assert_eq!(original.otp, decoded.otp);
         assert_eq!(original.mask, decoded.mask);
@@ -185,4 +587,49 @@ mod tests {
         let rt = ct.gen_req_token();
         ct.verify_req_token(rt).unwrap();
     }
+
+    #[test]
+    fn custom_codec_round_trip() {
+        let ct = CookieToken::new();
+        let s = ct.encode_with(&Hex);
+        assert_eq!(s.len(), 64);
+        let ct = CookieToken::decode_with(&s, &Hex).unwrap();
+        let rt = ct.gen_req_token().encode_with(&Hex);
+        assert_eq!(rt.len(), 128);
+        ct.verify_req_token(RequestToken::decode_with(&rt, &Hex).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(Error::InvalidToken.code(), "xsrf.malformed");
+        assert_eq!(Error::TokenMismatch.code(), "xsrf.mismatch");
+        assert_eq!(Error::Expired.code(), "xsrf.expired");
+        assert_eq!(Error::WrongEnvironment.code(), "xsrf.wrong_environment");
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let ct = CookieToken::from_bytes(*CookieToken::new().as_bytes());
+        let rt = RequestToken::from_bytes(&ct.gen_req_token().to_bytes());
+        ct.verify_req_token(rt).unwrap();
+    }
+
+    static VECTOR_COOKIE: CookieToken = CookieToken::from_bytes([7; 32]);
+
+    const VECTOR_REQUEST: RequestToken = RequestToken::from_bytes(&{
+        // an otp of all ones, masking a cookie token of all sevens.
+        let mut b = [1; 64];
+        let mut i = 32;
+        while i < 64 {
+            b[i] = 6;
+            i += 1;
+        }
+        b
+    });
+
+    #[test]
+    fn const_vectors() {
+        VECTOR_COOKIE.verify_req_token(VECTOR_REQUEST).unwrap();
+    }
 }
@@ -0,0 +1,192 @@
+//! Absolute and idle timeouts for cookie tokens.
+//!
+//! A `CookieToken` on its own never expires. A [`CookieLifetime`] seals it
+//! into a cookie value along with the time it was created and last used,
+//! authenticated with `HMAC(key, ...)`, the way session cookies usually
+//! are:
+//!
+//! ```text
+//! base64(<cookie token><created><last used><mac>)
+//! ```
+//!
+//! Verification enforces both timeouts and the request token in one call,
+//! and returns the cookie value with its idle timeout extended, to be sent
+//! back to the client.
+use crate::sha256::{hmac, DIGEST_LEN};
+use crate::{
+    Clock, CookieToken, CsrfProtection, Error, Key, RequestToken, Result, SystemClock, Verified,
+    TOKEN_LEN,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+const DOMAIN: &[u8] = b"xsrf cookie lifetime\0";
+
+const SEALED_LEN: usize = TOKEN_LEN + 8 + 8 + DIGEST_LEN;
+
+/// Seals cookie tokens with an absolute and an idle timeout.
+#[derive(Clone, Debug)]
+pub struct CookieLifetime {
+    key: Key,
+    absolute: Duration,
+    idle: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl CookieLifetime {
+    /// Cookie tokens sealed with `key` expire `absolute` after they were
+    /// created, or `idle` after they were last verified, whichever is first.
+    pub fn new(key: &Key, absolute: Duration, idle: Duration) -> CookieLifetime {
+        Self {
+            key: key.clone(),
+            absolute,
+            idle,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use the given clock instead of the system clock.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> CookieLifetime {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The cookie value for a newly created `cookie`.
+    pub fn seal(&self, cookie: &CookieToken) -> String {
+        let now = self.clock.now();
+        self.encode(cookie, now, now)
+    }
+
+    /// The cookie token in a sealed cookie value, if it has not expired.
+    /// This does not extend the idle timeout, and suits issuing request
+    /// tokens when rendering a page.
+    pub fn open(&self, value: &str) -> Result<CookieToken> {
+        self.decode(value).map(|(cookie, _)| cookie)
+    }
+
+    /// Check the sealed cookie value has not expired, and verify `token`
+    /// against its cookie token with `protection`. On success, returns the
+    /// verified token, and the cookie value with the idle timeout extended
+    /// from now.
+    pub fn verify(
+        &self,
+        value: &str,
+        token: RequestToken,
+        protection: &dyn CsrfProtection,
+    ) -> Result<(Verified<RequestToken>, String)> {
+        let (cookie, created) = self.decode(value)?;
+        let verified = protection.verified(&cookie, token)?;
+        Ok((verified, self.encode(&cookie, created, self.clock.now())))
+    }
+
+    fn mac(&self, data: &[u8]) -> [u8; DIGEST_LEN] {
+        hmac(self.key.as_bytes(), &[DOMAIN, data])
+    }
+
+    fn encode(&self, cookie: &CookieToken, created: u64, used: u64) -> String {
+        let mut data = Vec::with_capacity(SEALED_LEN);
+        data.extend_from_slice(cookie.as_bytes());
+        data.extend_from_slice(&created.to_be_bytes());
+        data.extend_from_slice(&used.to_be_bytes());
+        let mac = self.mac(&data);
+        data.extend_from_slice(&mac);
+        base64::encode_config(&data, base64::URL_SAFE)
+    }
+
+    /// The cookie token and creation time in a sealed cookie value.
+    fn decode(&self, value: &str) -> Result<(CookieToken, u64)> {
+        let data =
+            base64::decode_config(value, base64::URL_SAFE).map_err(|_| Error::InvalidToken)?;
+        if data.len() != SEALED_LEN {
+            return Err(Error::InvalidToken);
+        }
+        let (body, mac) = data.split_at(SEALED_LEN - DIGEST_LEN);
+        if !bool::from(self.mac(body).ct_eq(mac)) {
+            return Err(Error::TokenMismatch);
+        }
+        let mut cookie = [0; TOKEN_LEN];
+        cookie.copy_from_slice(&body[..TOKEN_LEN]);
+        let created = read_u64(&body[TOKEN_LEN..TOKEN_LEN + 8]);
+        let used = read_u64(&body[TOKEN_LEN + 8..]);
+        let now = self.clock.now();
+        let expired = |from: u64, limit: Duration| now.saturating_sub(from) > limit.as_secs();
+        if expired(created, self.absolute) || expired(used, self.idle) {
+            return Err(Error::Expired);
+        }
+        Ok((CookieToken::from_bytes(cookie), created))
+    }
+}
+
+fn read_u64(b: &[u8]) -> u64 {
+    let mut out = [0; 8];
+    out.copy_from_slice(b);
+    u64::from_be_bytes(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieLifetime;
+    use crate::{CookieToken, CsrfProtection, Error, Key, Plain, TestClock};
+    use std::time::Duration;
+
+    #[test]
+    fn absolute_and_idle_timeouts() {
+        let clock = TestClock::new(1_000_000);
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let lifetime = CookieLifetime::new(&Key::generate(), minutes(60), minutes(10))
+            .with_clock(clock.clone());
+        let ct = CookieToken::new();
+        let mut value = lifetime.seal(&ct);
+        assert_eq!(lifetime.open(&value).unwrap().as_bytes(), ct.as_bytes());
+
+        // each verification extends the idle timeout, up to the absolute one.
+        for _ in 0..6 {
+            clock.advance(minutes(9));
+            value = lifetime
+                .verify(&value, Plain.issue_req_token(&ct), &Plain)
+                .unwrap()
+                .1;
+        }
+        clock.advance(minutes(7));
+        assert_eq!(lifetime.open(&value).err(), Some(Error::Expired));
+
+        // an idle cookie expires even within the absolute timeout.
+        let value = lifetime.seal(&ct);
+        clock.advance(minutes(11));
+        assert_eq!(
+            lifetime.verify(&value, ct.gen_req_token(), &Plain).err(),
+            Some(Error::Expired)
+        );
+    }
+
+    #[test]
+    fn sealed_values_are_authenticated() {
+        let lifetime = CookieLifetime::new(
+            &Key::generate(),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let ct = CookieToken::new();
+        let other = CookieLifetime::new(
+            &Key::generate(),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            lifetime.open(&other.seal(&ct)).err(),
+            Some(Error::TokenMismatch)
+        );
+        assert_eq!(
+            lifetime.open(&ct.to_string()).err(),
+            Some(Error::InvalidToken)
+        );
+        let value = lifetime.seal(&ct);
+        assert_eq!(
+            lifetime
+                .verify(&value, CookieToken::new().gen_req_token(), &Plain)
+                .err(),
+            Some(Error::TokenMismatch)
+        );
+    }
+}
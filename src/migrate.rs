@@ -0,0 +1,274 @@
+//! Moving from an anonymous token to an authenticated one.
+//!
+//! On login the `CookieToken` must be replaced, or a token planted before
+//! login stays valid after it. Users who were in the middle of a form in
+//! another tab would then be rejected, so a [`Migration`] keeps accepting
+//! request tokens of the anonymous `CookieToken` for a short grace window,
+//! but only those issued before the migration. This needs an `XsrfConfig`
+//! issuing timestamps, such as one with a TTL; without them, no request
+//! token of the anonymous `CookieToken` is accepted.
+//!
+//! The timestamp is only bound to the anonymous `CookieToken`, so whoever
+//! planted it can still backdate tokens, until they expire. Keep the grace
+//! window and TTL short.
+//!
+//! Rotating a token for other reasons, such as periodically, works the same
+//! way with `CookieToken::rotate`, whose [`TransitionVerifier`] accepts
+//! request tokens of the old token until an explicit cutoff.
+//!
+//! The new token is freshly random, since deriving it from the anonymous one
+//! would let whoever planted that predict it, and is issued in the
+//! authenticated user's `TokenFamily`, so revoking the user's family covers
+//! it. To also bind request tokens to the user, combine this with
+//! `SignedDoubleSubmit`. A rotated token stays in the family of the one it
+//! replaces.
+use crate::{
+    AuditEvent, CookieToken, RequestToken, Result, TokenFamily, VerifyOutcome, XsrfConfig,
+};
+use std::time::Duration;
+
+/// A new `CookieToken`, along with the one it replaced.
+pub struct Migration {
+    current: CookieToken,
+    previous: CookieToken,
+    migrated_at: Option<u64>,
+    grace_until: u64,
+}
+
+impl CookieToken {
+    /// Replace this token with a new one in the authenticated user's
+    /// `family`, accepting request tokens issued from this one before now
+    /// for `grace` longer.
+    pub fn migrate(self, family: &TokenFamily, grace: Duration, config: &XsrfConfig) -> Migration {
+        let current = self.replaced_by(CookieToken::new_in_family(family), config);
+        let now = config.now();
+        Migration {
+            current,
+            previous: self,
+            migrated_at: Some(now),
+            grace_until: now.saturating_add(grace.as_secs()),
+        }
+    }
+}
+
+/// Accepts request tokens of a rotated `CookieToken` alongside those of its
+/// replacement, until a cutoff.
+pub struct TransitionVerifier {
+    migration: Migration,
+}
+
+impl CookieToken {
+    /// Rotate to a new token, returned along with a verifier accepting
+    /// request tokens of either until `cutoff`, in seconds since the unix
+    /// epoch. After the cutoff, only request tokens of the new one verify.
+    pub fn rotate(&self, cutoff: u64, config: &XsrfConfig) -> (CookieToken, TransitionVerifier) {
+        let new = self.replaced_by(CookieToken::new_in_family(&self.family()), config);
+        let migration = Migration {
+            current: new.duplicate(),
+            previous: self.duplicate(),
+            migrated_at: None,
+            grace_until: cutoff,
+        };
+        (new, TransitionVerifier { migration })
+    }
+
+    /// Record `new` as replacing this token.
+    fn replaced_by(&self, new: CookieToken, config: &XsrfConfig) -> CookieToken {
+        config.audit(|| AuditEvent::KeyRotated {
+            from: self.family().audit_id(),
+            to: new.family().audit_id(),
+        });
+        new
+    }
+
+    /// A copy of this token, in the same family.
+    fn duplicate(&self) -> CookieToken {
+        CookieToken::from_bytes(*self.as_bytes()).with_family(&self.family())
+    }
+}
+
+impl TransitionVerifier {
+    /// Unix time in seconds until which request tokens of the old
+    /// `CookieToken` are accepted.
+    pub fn cutoff(&self) -> u64 {
+        self.migration.grace_until()
+    }
+
+    /// Verify the token against the new `CookieToken`, or against the old
+    /// one until the cutoff.
+    pub fn verify_req_token(&self, token: RequestToken, config: &XsrfConfig) -> Result<()> {
+        self.migration.verify_req_token(token, config)
+    }
+}
+
+impl Migration {
+    /// Restore a migration persisted using its accessors, such as in a
+    /// session across requests.
+    pub fn from_parts(
+        current: CookieToken,
+        previous: CookieToken,
+        migrated_at: u64,
+        grace_until: u64,
+    ) -> Migration {
+        Self {
+            current,
+            previous,
+            migrated_at: Some(migrated_at),
+            grace_until,
+        }
+    }
+
+    /// The new `CookieToken`, from which new request tokens are issued.
+    pub fn cookie_token(&self) -> &CookieToken {
+        &self.current
+    }
+
+    /// The replaced `CookieToken`.
+    pub fn previous_cookie_token(&self) -> &CookieToken {
+        &self.previous
+    }
+
+    /// Unix time in seconds of the migration, after which request tokens
+    /// issued from the replaced `CookieToken` are not accepted. This is
+    /// `None` for rotations, which accept them regardless.
+    pub fn migrated_at(&self) -> Option<u64> {
+        self.migrated_at
+    }
+
+    /// Unix time in seconds until which request tokens of the replaced
+    /// `CookieToken` are accepted.
+    pub fn grace_until(&self) -> u64 {
+        self.grace_until
+    }
+
+    /// Verify the token against the new `CookieToken`, or against the
+    /// replaced one during the grace window, if it was issued before the
+    /// migration.
+    pub fn verify_req_token(&self, token: RequestToken, config: &XsrfConfig) -> Result<()> {
+        let mut cookie = &self.current;
+        let mut outcome = config.evaluate(cookie, &token, None);
+        let issued_before = match self.migrated_at {
+            Some(at) => token.claims.issued_at.is_some_and(|t| t <= at),
+            None => true,
+        };
+        if outcome == VerifyOutcome::Mismatch && issued_before && config.now() <= self.grace_until {
+            cookie = &self.previous;
+            outcome = config.evaluate(cookie, &token, None);
+        }
+        config.audit_outcome(cookie, outcome);
+        outcome.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AuditEvent, CookieToken, Error, MemoryAuditSink, MemoryRevocationStore, TestClock,
+        TokenFamily, XsrfConfig,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn config(clock: &TestClock) -> XsrfConfig {
+        XsrfConfig::new()
+            .with_clock(clock.clone())
+            .with_ttl(Duration::from_secs(600))
+    }
+
+    #[test]
+    fn grace_window() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock);
+        let anonymous = CookieToken::new();
+        let old = anonymous.gen_req_token_with(&config);
+        let m = anonymous.migrate(&TokenFamily::new(), Duration::from_secs(60), &config);
+        m.verify_req_token(m.cookie_token().gen_req_token_with(&config), &config)
+            .unwrap();
+        m.verify_req_token(old.clone(), &config).unwrap();
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(m.verify_req_token(old, &config), Err(Error::TokenMismatch));
+    }
+
+    #[test]
+    fn grace_window_only_for_earlier_tokens() {
+        let clock = TestClock::new(1_000_000);
+        let config = config(&clock);
+        let m = CookieToken::new().migrate(&TokenFamily::new(), Duration::from_secs(60), &config);
+        clock.advance(Duration::from_secs(1));
+        // whoever planted the anonymous token can't mint new ones with it.
+        let minted = m.previous_cookie_token().gen_req_token_with(&config);
+        assert_eq!(
+            m.verify_req_token(minted, &config),
+            Err(Error::TokenMismatch)
+        );
+        let untimed = m.previous_cookie_token().gen_req_token();
+        assert_eq!(
+            m.verify_req_token(untimed, &config),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn rotate_until_cutoff() {
+        let clock = TestClock::new(1_000_000);
+        let config = XsrfConfig::new().with_clock(clock.clone());
+        let old = CookieToken::new();
+        let (new, verifier) = old.rotate(1_000_100, &config);
+        let old_rt = old.gen_req_token();
+        verifier.verify_req_token(old_rt.clone(), &config).unwrap();
+        verifier
+            .verify_req_token(new.gen_req_token(), &config)
+            .unwrap();
+        clock.set(1_000_101);
+        assert_eq!(
+            verifier.verify_req_token(old_rt, &config),
+            Err(Error::TokenMismatch)
+        );
+        verifier
+            .verify_req_token(new.gen_req_token(), &config)
+            .unwrap();
+    }
+
+    #[test]
+    fn rotations_stay_in_the_family() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let store = Arc::new(MemoryRevocationStore::new());
+        let config = XsrfConfig::new()
+            .with_audit_sink(sink.clone())
+            .with_revocation_store(store.clone());
+        let family = TokenFamily::new();
+        let old = CookieToken::new_in_family(&family);
+        let (new, verifier) = old.rotate(u64::MAX, &config);
+        let id = family.audit_id();
+        assert_eq!(sink.events(), [AuditEvent::KeyRotated { from: id, to: id }]);
+        store.revoke(family);
+        for rt in [new.gen_req_token(), old.gen_req_token()] {
+            assert_eq!(verifier.verify_req_token(rt, &config), Err(Error::Revoked));
+        }
+    }
+
+    #[test]
+    fn migrations_move_to_the_users_family() {
+        let store = Arc::new(MemoryRevocationStore::new());
+        let config = XsrfConfig::new().with_revocation_store(store.clone());
+        let anonymous = CookieToken::new();
+        let anonymous_family = anonymous.family();
+        let user = TokenFamily::new();
+        let m = anonymous.migrate(&user, Duration::from_secs(60), &config);
+        let ct = m.cookie_token();
+        assert_eq!(ct.family(), user);
+        assert_ne!(
+            &ct.as_bytes()[..16],
+            &m.previous_cookie_token().as_bytes()[..16]
+        );
+        store.revoke(anonymous_family);
+        ct.verify_req_token_with(ct.gen_req_token(), &config)
+            .unwrap();
+        // signing the user out of other devices covers the migrated token.
+        store.revoke(user);
+        assert_eq!(
+            m.verify_req_token(ct.gen_req_token(), &config),
+            Err(Error::Revoked)
+        );
+    }
+}
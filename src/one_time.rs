@@ -0,0 +1,69 @@
+//! Request tokens that can only be verified once.
+use crate::{CookieToken, RequestToken, Result, Verified, XsrfConfig};
+
+/// A request token whose verification consumes it.
+///
+/// This is deliberately not `Clone`, so within a process a given `OneTime`
+/// can be verified at most once. Across processes, verification records the
+/// nonce in the replay store as part of the same step.
+pub struct OneTime<T> {
+    token: T,
+}
+
+impl OneTime<RequestToken> {
+    pub fn new(token: RequestToken) -> OneTime<RequestToken> {
+        Self { token }
+    }
+
+    /// Verify the token against `cookie` and `config`, recording it as used.
+    ///
+    /// # Panics
+    ///
+    /// If `config` has no replay store, since the token could then be used
+    /// again.
+    pub fn verify(
+        self,
+        cookie: &CookieToken,
+        config: &XsrfConfig,
+    ) -> Result<Verified<RequestToken>> {
+        assert!(
+            config.has_replay_store(),
+            "OneTime tokens require a replay store"
+        );
+        config.verify(cookie, &self.token, None)?;
+        Ok(Verified::new(self.token))
+    }
+}
+
+impl From<RequestToken> for OneTime<RequestToken> {
+    fn from(token: RequestToken) -> Self {
+        Self::new(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OneTime;
+    use crate::{CookieToken, Error, MemoryReplayStore, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn one_time_is_consumed() {
+        let config =
+            XsrfConfig::new().with_replay_store(MemoryReplayStore::new(Duration::from_secs(60)));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        OneTime::new(rt.clone()).verify(&ct, &config).unwrap();
+        assert_eq!(
+            OneTime::from(rt).verify(&ct, &config).err(),
+            Some(Error::Replayed)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn one_time_requires_replay_store() {
+        let ct = CookieToken::new();
+        let _ = OneTime::new(ct.gen_req_token()).verify(&ct, &XsrfConfig::new());
+    }
+}
@@ -0,0 +1,95 @@
+//! Detailed verification outcomes for logging.
+use crate::{CookieToken, Error, RequestToken, Result, XsrfConfig};
+
+/// The precise result of verifying a request token.
+///
+/// Every check is evaluated regardless of the others, so the time taken does
+/// not reveal which one failed. The outcome is meant for operators' logs;
+/// clients should only ever see a generic failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyOutcome {
+    Valid,
+    /// The token's claims are malformed, such as a missing or future issue
+    /// time.
+    Malformed,
+    /// The token is not derived from the `CookieToken`.
+    Mismatch,
+    Expired,
+    /// The token was issued for a different purpose.
+    WrongPurpose,
+    /// The token was issued under a different cookie scope.
+    WrongCookieScope,
+    /// The token was already used.
+    Replayed,
+    /// The token's family was revoked.
+    Revoked,
+    /// The token was issued in a different deployment environment.
+    WrongEnvironment,
+    /// The token was minted for a different single sign-on audience.
+    WrongAudience,
+}
+
+impl VerifyOutcome {
+    pub fn is_valid(&self) -> bool {
+        *self == VerifyOutcome::Valid
+    }
+
+    pub fn into_result(self) -> Result<()> {
+        match self {
+            VerifyOutcome::Valid => Ok(()),
+            VerifyOutcome::Malformed => Err(Error::InvalidToken),
+            VerifyOutcome::Mismatch => Err(Error::TokenMismatch),
+            VerifyOutcome::Expired => Err(Error::Expired),
+            VerifyOutcome::WrongPurpose => Err(Error::WrongPurpose),
+            VerifyOutcome::WrongCookieScope | VerifyOutcome::WrongAudience => {
+                Err(Error::WrongScope)
+            }
+            VerifyOutcome::Replayed => Err(Error::Replayed),
+            VerifyOutcome::Revoked => Err(Error::Revoked),
+            VerifyOutcome::WrongEnvironment => Err(Error::WrongEnvironment),
+        }
+    }
+}
+
+impl CookieToken {
+    /// Verify like `verify_req_token_with`, reporting the precise outcome.
+    pub fn verify_req_token_outcome(
+        &self,
+        token: &RequestToken,
+        config: &XsrfConfig,
+    ) -> VerifyOutcome {
+        config.outcome(self, token, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifyOutcome;
+    use crate::{CookieToken, TestClock, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn outcomes() {
+        let clock = TestClock::new(1_000_000);
+        let config = XsrfConfig::new()
+            .with_clock(clock.clone())
+            .with_ttl(Duration::from_secs(60));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_with(&config);
+        assert!(ct.verify_req_token_outcome(&rt, &config).is_valid());
+        assert_eq!(
+            CookieToken::new().verify_req_token_outcome(&rt, &config),
+            VerifyOutcome::Mismatch
+        );
+        assert_eq!(
+            ct.verify_req_token_outcome(&ct.gen_req_token(), &config),
+            VerifyOutcome::Malformed
+        );
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(
+            ct.verify_req_token_outcome(&rt, &config),
+            VerifyOutcome::Expired
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! The per-page secrets shared by CSRF protection and a Content Security
+//! Policy.
+//!
+//! Pages with a strict CSP need a random script nonce, and pages with forms
+//! need a request token. [`PageSecrets`] draws the randomness for both in a
+//! single pass from the configured `Entropy`, so apps don't need a second
+//! randomness utility just for the nonce.
+use crate::{Base64Url, CookieToken, TokenCodec, XsrfConfig, TOKEN_LEN};
+
+/// Length of the raw bytes of a CSP nonce.
+const NONCE_LEN: usize = 18;
+
+/// A request token and a CSP nonce for rendering one page.
+#[derive(Clone, Debug)]
+pub struct PageSecrets {
+    token: String,
+    nonce: String,
+}
+
+impl PageSecrets {
+    /// Generate the secrets for a page, issuing the request token from
+    /// `cookie` with `config`.
+    pub fn new(cookie: &CookieToken, config: &XsrfConfig) -> PageSecrets {
+        let mut random = [0; TOKEN_LEN + NONCE_LEN];
+        config.entropy().fill(&mut random);
+        let mut otp = [0; TOKEN_LEN];
+        otp.copy_from_slice(&random[..TOKEN_LEN]);
+        let token = config.issue_with_otp(cookie, otp, config.claims());
+        let mut nonce = String::with_capacity(Base64Url.encoded_len(NONCE_LEN));
+        Base64Url.encode(&random[TOKEN_LEN..], &mut nonce);
+        Self {
+            token: token.to_string(),
+            nonce,
+        }
+    }
+
+    /// The request token, encoded.
+    pub fn request_token(&self) -> &str {
+        &self.token
+    }
+
+    /// The CSP nonce, for the `nonce` attribute of `<script>` and `<style>`
+    /// elements.
+    pub fn csp_nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// The nonce as a CSP source expression, for the `script-src` directive
+    /// of the `Content-Security-Policy` header.
+    pub fn csp_source(&self) -> String {
+        format!("'nonce-{}'", self.nonce)
+    }
+
+    /// A hidden form input named `field` carrying the request token.
+    pub fn hidden_input(&self, field: &str) -> String {
+        format!(
+            r#"<input type="hidden" name="{}" value="{}">"#,
+            escape(field),
+            self.token
+        )
+    }
+
+    /// A hidden honeypot input named `field`, to render alongside the
+    /// token. People never see it, but bots filling in every field do, and
+    /// `Extractor::with_honeypot` rejects those submissions. It is hidden
+    /// with an attribute rather than a style, which a strict CSP may block.
+    pub fn honeypot_input(field: &str) -> String {
+        format!(
+            r#"<input type="text" name="{}" value="" tabindex="-1" autocomplete="off" aria-hidden="true" hidden>"#,
+            escape(field)
+        )
+    }
+
+    /// A `<meta>` tag named `name` carrying the request token, for scripts.
+    pub fn meta_tag(&self, name: &str) -> String {
+        format!(r#"<meta name="{}" content="{}">"#, escape(name), self.token)
+    }
+}
+
+/// Escape an attribute value. Tokens and nonces never need it.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageSecrets;
+    use crate::{CookieToken, RequestToken, XsrfConfig};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn page_secrets() {
+        let ct = CookieToken::new();
+        let page = PageSecrets::new(&ct, &XsrfConfig::new());
+        let rt = RequestToken::try_from(page.request_token()).unwrap();
+        ct.verify_req_token(rt).unwrap();
+        assert_eq!(page.csp_nonce().len(), 24);
+        assert_eq!(page.csp_source(), format!("'nonce-{}'", page.csp_nonce()));
+        assert_eq!(
+            page.hidden_input("x\"y"),
+            format!(
+                r#"<input type="hidden" name="x&quot;y" value="{}">"#,
+                page.request_token()
+            )
+        );
+        let other = PageSecrets::new(&ct, &XsrfConfig::new());
+        assert_ne!(page.csp_nonce(), other.csp_nonce());
+        assert!(PageSecrets::honeypot_input("website").contains(r#"name="website" value="""#));
+    }
+}
@@ -0,0 +1,42 @@
+//! Percent encoding and decoding.
+
+/// Percent decode `input` into a string, optionally treating `+` as a space
+/// as urlencoded forms do. Fails on malformed escapes or invalid UTF-8.
+pub(crate) fn decode(input: &[u8], plus_as_space: bool) -> Option<String> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' if plus_as_space => out.push(b' '),
+            b'%' => {
+                let hex = input.get(i + 1..i + 3)?;
+                // from_str_radix alone would also accept a sign, as in `%+1`.
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let hex = std::str::from_utf8(hex).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Percent encode `input` for use in a urlencoded form, leaving only
+/// unreserved characters as is.
+#[cfg(feature = "test-client")]
+pub(crate) fn encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
@@ -0,0 +1,268 @@
+//! Deciding which requests must carry a valid request token.
+use crate::percent;
+use crate::request::{media_type, Request};
+
+const SAFE_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS", "TRACE"];
+
+/// Declares which requests are exempt from verification.
+///
+/// Integrations consult `requires_token` before verifying a request. The
+/// rules are applied in order:
+///
+/// 1. Requests to an always protected path require a token.
+/// 2. Requests to an exempt path do not.
+/// 3. Requests authenticated by an exempt non-cookie means do not.
+/// 4. Requests with an exempt content type do not.
+/// 5. Requests using a method with an override follow the override.
+/// 6. Requests using a safe method (`GET`, `HEAD`, `OPTIONS`, `TRACE`) do
+///    not, and all others do.
+///
+/// Paths match on whole segments, so `/api` matches `/api` and `/api/users`
+/// but not `/apix`. Request paths are percent decoded, and repeated slashes
+/// and dot segments collapsed, before matching, so `/api//admin` and
+/// `/api/./%61dmin` match `/api/admin`. Paths that fail to decode always
+/// require a token. Prefixes are matched as given, and should be normalized.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    protected_paths: Vec<String>,
+    exempt_paths: Vec<String>,
+    exempt_content_types: Vec<String>,
+    methods: Vec<(String, bool)>,
+    bearer_exempt: bool,
+    service_cert_header: Option<String>,
+}
+
+impl Policy {
+    pub fn new() -> Policy {
+        Self::default()
+    }
+
+    /// Always require a token for paths under `prefix`, regardless of the
+    /// other rules.
+    pub fn with_protected_path(mut self, prefix: &str) -> Policy {
+        self.protected_paths.push(prefix.to_owned());
+        self
+    }
+
+    /// Do not require a token for paths under `prefix`.
+    pub fn with_exempt_path(mut self, prefix: &str) -> Policy {
+        self.exempt_paths.push(prefix.to_owned());
+        self
+    }
+
+    /// Do not require a token for requests authenticated with an
+    /// `Authorization: Bearer` header, such as from other services. Forms
+    /// and simple cross-site requests can't set the header, so these can't
+    /// be forged. Other schemes, such as `Basic`, are still protected, since
+    /// browsers remember and resend them on their own.
+    ///
+    /// Requests also carrying a `Cookie` header are still protected, since
+    /// the app may fall back to the cookie to authenticate them. The app
+    /// must authenticate exempt requests by the bearer token alone.
+    pub fn with_bearer_exempt(mut self) -> Policy {
+        self.bearer_exempt = true;
+        self
+    }
+
+    /// Do not require a token for requests from other services,
+    /// authenticated with a TLS client certificate the proxy terminating TLS
+    /// reports in `header`.
+    ///
+    /// Browsers present client certificates on cross-site requests on their
+    /// own, so like cookies they are ambient credentials. The proxy must
+    /// only set the header for certificates of a CA issuing to services,
+    /// never to people, and must remove it from incoming requests. Requests
+    /// carrying an `Origin`, `Sec-Fetch-Site` or `Cookie` header come from
+    /// a browser, and are still protected.
+    pub fn with_service_cert_exempt(mut self, header: &str) -> Policy {
+        self.service_cert_header = Some(header.to_owned());
+        self
+    }
+
+    /// Do not require a token for requests with the given media type, such
+    /// as `application/grpc`. Parameters are ignored.
+    pub fn with_exempt_content_type(mut self, content_type: &str) -> Policy {
+        self.exempt_content_types.push(media_type(content_type));
+        self
+    }
+
+    /// Override whether requests using `method` require a token.
+    pub fn with_method(mut self, method: &str, protected: bool) -> Policy {
+        self.methods.push((method.to_ascii_uppercase(), protected));
+        self
+    }
+
+    /// Whether the request must carry a valid request token.
+    pub fn requires_token<R: Request + ?Sized>(&self, req: &R) -> bool {
+        let path = match normalize_path(req.path()) {
+            Some(path) => path,
+            None => return true,
+        };
+        if self.protected_paths.iter().any(|p| path_matches(p, &path)) {
+            return true;
+        }
+        if self.exempt_paths.iter().any(|p| path_matches(p, &path)) {
+            return false;
+        }
+        if self.authenticated_without_cookie(req) {
+            return false;
+        }
+        if let Some(ct) = req.header("content-type") {
+            let ct = media_type(ct);
+            if self.exempt_content_types.contains(&ct) {
+                return false;
+            }
+        }
+        let method = req.method();
+        if let Some((_, protected)) = self
+            .methods
+            .iter()
+            .find(|(m, _)| m.eq_ignore_ascii_case(method))
+        {
+            return *protected;
+        }
+        !is_safe_method(method)
+    }
+
+    fn authenticated_without_cookie<R: Request + ?Sized>(&self, req: &R) -> bool {
+        if req.header("cookie").is_some() {
+            return false;
+        }
+        let bearer = self.bearer_exempt
+            && req.header("authorization").is_some_and(|v| {
+                let mut parts = v.trim().splitn(2, ' ');
+                parts
+                    .next()
+                    .is_some_and(|s| s.eq_ignore_ascii_case("bearer"))
+                    && parts.next().is_some_and(|t| !t.trim().is_empty())
+            });
+        let browser = req.header("origin").is_some() || req.header("sec-fetch-site").is_some();
+        let service_cert = !browser
+            && self
+                .service_cert_header
+                .as_ref()
+                .and_then(|h| req.header(h))
+                .is_some_and(|v| !v.trim().is_empty());
+        bearer || service_cert
+    }
+}
+
+/// Whether `method` is safe, and so must not change state.
+pub(crate) fn is_safe_method(method: &str) -> bool {
+    SAFE_METHODS.iter().any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Percent decode `path`, collapsing repeated slashes and resolving dot
+/// segments, the way the router serving it likely will.
+fn normalize_path(path: &str) -> Option<String> {
+    let decoded = percent::decode(path.as_bytes(), false)?;
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    let mut out = String::with_capacity(decoded.len());
+    for s in &segments {
+        out.push('/');
+        out.push_str(s);
+    }
+    let dir = matches!(decoded.rsplit('/').next(), Some("" | "." | ".."));
+    if out.is_empty() || dir {
+        out.push('/');
+    }
+    Some(out)
+}
+
+fn path_matches(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Policy;
+    use crate::request::tests::TestRequest;
+
+    #[test]
+    fn default_protects_unsafe_methods() {
+        let p = Policy::new();
+        assert!(!p.requires_token(&TestRequest::new("GET", "/")));
+        assert!(!p.requires_token(&TestRequest::new("head", "/")));
+        assert!(p.requires_token(&TestRequest::new("POST", "/")));
+        assert!(p.requires_token(&TestRequest::new("DELETE", "/")));
+    }
+
+    #[test]
+    fn exemptions_and_overrides() {
+        let p = Policy::new()
+            .with_exempt_path("/api")
+            .with_protected_path("/api/admin")
+            .with_protected_path("/logout")
+            .with_exempt_content_type("application/grpc")
+            .with_method("PROPFIND", false);
+        assert!(!p.requires_token(&TestRequest::new("POST", "/api/users")));
+        assert!(p.requires_token(&TestRequest::new("POST", "/apix")));
+        assert!(p.requires_token(&TestRequest::new("POST", "/api/admin/x")));
+        assert!(p.requires_token(&TestRequest::new("GET", "/logout")));
+        assert!(!p.requires_token(&TestRequest::new("PROPFIND", "/")));
+        assert!(!p.requires_token(
+            &TestRequest::new("POST", "/svc").header("Content-Type", "Application/GRPC; charset=x")
+        ));
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        let p = Policy::new()
+            .with_exempt_path("/api")
+            .with_protected_path("/api/admin");
+        for path in [
+            "/api//admin",
+            "/api/./admin/x",
+            "/api/users/../admin",
+            "/api/%61dmin",
+            "/api%2Fadmin",
+            "/api/%zz",
+        ] {
+            assert!(
+                p.requires_token(&TestRequest::new("POST", path)),
+                "{}",
+                path
+            );
+        }
+        assert!(!p.requires_token(&TestRequest::new("POST", "//api/users/")));
+        assert!(!p.requires_token(&TestRequest::new("POST", "/x/../api")));
+    }
+
+    #[test]
+    fn bearer_authentication() {
+        let p = Policy::new().with_bearer_exempt();
+        let post = || TestRequest::new("POST", "/");
+        assert!(!p.requires_token(&post().header("Authorization", "Bearer abc")));
+        assert!(p.requires_token(&post().header("Authorization", "Basic YTpi")));
+        assert!(p.requires_token(&post().header("Authorization", "Bearer ")));
+        assert!(p.requires_token(
+            &post()
+                .header("Authorization", "Bearer abc")
+                .header("Cookie", "session=x")
+        ));
+        assert!(Policy::new().requires_token(&post().header("Authorization", "Bearer abc")));
+    }
+
+    #[test]
+    fn service_certs_only_outside_browsers() {
+        let p = Policy::new().with_service_cert_exempt("X-Service-Cert");
+        let post = || TestRequest::new("POST", "/").header("X-Service-Cert", "CN=billing");
+        assert!(!p.requires_token(&post()));
+        // a browser presenting a certificate on a cross-site form post.
+        assert!(p.requires_token(&post().header("Origin", "https://evil.example")));
+        assert!(p.requires_token(&post().header("Sec-Fetch-Site", "cross-site")));
+        assert!(p.requires_token(&post().header("Cookie", "session=x")));
+    }
+}
@@ -0,0 +1,82 @@
+//! Pre-generated one-time-pads for issuing request tokens.
+//!
+//! Filling the one-time-pad is the only costly part of issuing a plain
+//! request token. An [`OtpPool`] moves that work to a background thread, so
+//! the hot path is a pop and a xor.
+use crate::claims::Claims;
+use crate::{CookieToken, RequestToken, TOKEN_LEN};
+use rand::{thread_rng, Rng};
+use std::fmt;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Mutex;
+
+/// A bounded pool of random one-time-pads, refilled by a background thread.
+///
+/// The thread exits once the pool is dropped. Popping never blocks: when the
+/// pool is empty or contended, a pad is generated in place instead.
+pub struct OtpPool {
+    rx: Mutex<Receiver<[u8; TOKEN_LEN]>>,
+}
+
+impl OtpPool {
+    /// Start a pool holding up to `capacity` pads.
+    pub fn new(capacity: usize) -> OtpPool {
+        let (tx, rx) = sync_channel(capacity.max(1));
+        std::thread::Builder::new()
+            .name("xsrf-otp-pool".to_owned())
+            .spawn(move || {
+                let mut rng = thread_rng();
+                loop {
+                    let mut otp = [0; TOKEN_LEN];
+                    rng.fill(&mut otp);
+                    if tx.send(otp).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn xsrf otp pool thread");
+        Self { rx: Mutex::new(rx) }
+    }
+
+    pub(crate) fn pop(&self) -> [u8; TOKEN_LEN] {
+        if let Ok(rx) = self.rx.try_lock() {
+            if let Ok(otp) = rx.try_recv() {
+                return otp;
+            }
+        }
+        let mut otp = [0; TOKEN_LEN];
+        thread_rng().fill(&mut otp);
+        otp
+    }
+}
+
+impl fmt::Debug for OtpPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OtpPool(..)")
+    }
+}
+
+impl CookieToken {
+    /// Generate a request token like `gen_req_token`, taking the
+    /// one-time-pad from `pool`.
+    pub fn gen_req_token_pooled(&self, pool: &OtpPool) -> RequestToken {
+        self.issue_with_otp(pool.pop(), Claims::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OtpPool;
+    use crate::CookieToken;
+
+    #[test]
+    fn pooled_tokens_verify() {
+        let pool = OtpPool::new(4);
+        let ct = CookieToken::new();
+        let tokens: Vec<_> = (0..16).map(|_| ct.gen_req_token_pooled(&pool)).collect();
+        assert_ne!(tokens[0].otp, tokens[1].otp);
+        tokens
+            .into_iter()
+            .for_each(|rt| ct.verify_req_token(rt).unwrap());
+    }
+}
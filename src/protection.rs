@@ -0,0 +1,81 @@
+//! A common interface over the protection strategies.
+//!
+//! Framework integrations can be written once against [`CsrfProtection`],
+//! allowing users to swap strategies via configuration. Strategies needing
+//! per-request context, such as a session identifier, take it when they are
+//! constructed.
+use crate::{CookieToken, RequestToken, Result, Verified, XsrfConfig};
+
+/// Issues and verifies tokens.
+pub trait CsrfProtection: Send + Sync {
+    /// Issue a new `CookieToken`.
+    fn issue_cookie_token(&self) -> CookieToken {
+        CookieToken::new()
+    }
+
+    /// Issue a `RequestToken` derived from `cookie`.
+    fn issue_req_token(&self, cookie: &CookieToken) -> RequestToken;
+
+    /// Verify a `RequestToken` against the `cookie` it should be derived
+    /// from.
+    fn verify(&self, cookie: &CookieToken, token: RequestToken) -> Result<()>;
+
+    /// Verify like `verify`, returning the token as proof.
+    fn verified(
+        &self,
+        cookie: &CookieToken,
+        token: RequestToken,
+    ) -> Result<Verified<RequestToken>> {
+        self.verify(cookie, token.clone())?;
+        Ok(Verified::new(token))
+    }
+}
+
+/// The original strategy, issuing tokens without claims.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Plain;
+
+impl CsrfProtection for Plain {
+    fn issue_req_token(&self, cookie: &CookieToken) -> RequestToken {
+        cookie.gen_req_token()
+    }
+
+    fn verify(&self, cookie: &CookieToken, token: RequestToken) -> Result<()> {
+        cookie.verify_req_token(token)
+    }
+}
+
+impl CsrfProtection for XsrfConfig {
+    fn issue_req_token(&self, cookie: &CookieToken) -> RequestToken {
+        cookie.gen_req_token_with(self)
+    }
+
+    fn verify(&self, cookie: &CookieToken, token: RequestToken) -> Result<()> {
+        cookie.verify_req_token_with(token, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsrfProtection, Plain};
+    use crate::{Error, XsrfConfig};
+    use std::time::Duration;
+
+    fn round_trip(p: &dyn CsrfProtection) {
+        let ct = p.issue_cookie_token();
+        p.verify(&ct, p.issue_req_token(&ct)).unwrap();
+        let rt = p.verified(&ct, p.issue_req_token(&ct)).unwrap();
+        assert_eq!(rt.to_bytes(), rt.into_inner().to_bytes());
+        let other = p.issue_cookie_token();
+        assert_eq!(
+            p.verify(&other, p.issue_req_token(&ct)),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn strategies_round_trip() {
+        round_trip(&Plain);
+        round_trip(&XsrfConfig::new().with_ttl(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,170 @@
+//! Request tokens bound to a purpose.
+//!
+//! Unlike scoping tokens to a path, the purpose is part of the token itself,
+//! so it survives URL rewrites and lets one endpoint serve multiple distinct
+//! forms.
+//!
+//! Purposes known at compile time can be declared as marker types
+//! implementing [`Purpose`]. A [`PurposeToken`] for one of them cannot be
+//! passed where a token for another is expected.
+use crate::claims::MAX_FIELD_LEN;
+use crate::{CookieToken, Error, RequestToken, Result, XsrfConfig};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A purpose known at compile time, usually implemented by a zero-sized
+/// marker type.
+pub trait Purpose {
+    /// The purpose embedded in tokens, at most 255 bytes.
+    const NAME: &'static str;
+}
+
+/// A request token bound to the purpose `P`.
+pub struct PurposeToken<P: Purpose> {
+    token: RequestToken,
+    purpose: PhantomData<fn() -> P>,
+}
+
+impl<P: Purpose> PurposeToken<P> {
+    fn new(token: RequestToken) -> PurposeToken<P> {
+        Self {
+            token,
+            purpose: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> RequestToken {
+        self.token
+    }
+}
+
+impl<P: Purpose> fmt::Display for PurposeToken<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.token.fmt(f)
+    }
+}
+
+impl<P: Purpose> TryFrom<&str> for PurposeToken<P> {
+    type Error = Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        RequestToken::try_from(value).map(Self::new)
+    }
+}
+
+impl CookieToken {
+    /// Generate a request token bound to `purpose`.
+    ///
+    /// Panics if `purpose` is longer than 255 bytes.
+    pub fn gen_req_token_purpose(&self, purpose: &str) -> RequestToken {
+        self.gen_req_token_purpose_with(purpose, &XsrfConfig::default())
+    }
+
+    /// Generate a request token bound to `purpose`, also carrying the claims
+    /// required by `config`.
+    ///
+    /// Panics if `purpose` is longer than 255 bytes.
+    pub fn gen_req_token_purpose_with(&self, purpose: &str, config: &XsrfConfig) -> RequestToken {
+        assert!(purpose.len() <= MAX_FIELD_LEN, "xsrf purpose too long");
+        let mut claims = config.claims();
+        claims.purpose = Some(purpose.to_owned());
+        config.issue(self, claims)
+    }
+
+    /// Verify the token is derived from this `CookieToken` and was issued for
+    /// `expected_purpose`.
+    pub fn verify_req_token_purpose(
+        &self,
+        token: RequestToken,
+        expected_purpose: &str,
+    ) -> Result<()> {
+        self.verify_req_token_purpose_with(token, expected_purpose, &XsrfConfig::default())
+    }
+
+    /// Verify the token is derived from this `CookieToken`, was issued for
+    /// `expected_purpose`, and that it satisfies `config`.
+    pub fn verify_req_token_purpose_with(
+        &self,
+        token: RequestToken,
+        expected_purpose: &str,
+        config: &XsrfConfig,
+    ) -> Result<()> {
+        config.verify(self, &token, Some(expected_purpose))
+    }
+
+    /// Generate a request token bound to the purpose `P`.
+    pub fn gen_purpose_token<P: Purpose>(&self) -> PurposeToken<P> {
+        PurposeToken::new(self.gen_req_token_purpose(P::NAME))
+    }
+
+    /// Verify the token is derived from this `CookieToken` and was issued for
+    /// the purpose `P`. The purpose is still checked at runtime, since a
+    /// decoded token could have been issued for anything.
+    pub fn verify_purpose_token<P: Purpose>(&self, token: PurposeToken<P>) -> Result<()> {
+        self.verify_req_token_purpose(token.token, P::NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Purpose, PurposeToken};
+    use crate::{CookieToken, Error, RequestToken, TestClock, XsrfConfig};
+    use std::convert::{TryFrom, TryInto};
+    use std::time::Duration;
+
+    #[test]
+    fn purpose_round_trip() {
+        let ct = CookieToken::new();
+        let s = ct.gen_req_token_purpose("password-change").to_string();
+        let rt: RequestToken = s.as_str().try_into().unwrap();
+        ct.verify_req_token_purpose(rt.clone(), "password-change")
+            .unwrap();
+        assert_eq!(
+            ct.verify_req_token_purpose(rt, "checkout"),
+            Err(Error::WrongPurpose)
+        );
+        assert_eq!(
+            ct.verify_req_token_purpose(ct.gen_req_token(), "checkout"),
+            Err(Error::WrongPurpose)
+        );
+    }
+
+    #[test]
+    fn purpose_survives_renewal() {
+        let clock = TestClock::new(1_000_000);
+        let config = XsrfConfig::new()
+            .with_clock(clock.clone())
+            .with_ttl(Duration::from_secs(60));
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_purpose_with("checkout", &config);
+        clock.advance(Duration::from_secs(30));
+        let rt = rt.renew(&ct, &config).unwrap();
+        ct.verify_req_token_purpose_with(rt, "checkout", &config)
+            .unwrap();
+    }
+
+    struct Checkout;
+
+    impl Purpose for Checkout {
+        const NAME: &'static str = "checkout";
+    }
+
+    struct PasswordChange;
+
+    impl Purpose for PasswordChange {
+        const NAME: &'static str = "password-change";
+    }
+
+    #[test]
+    fn typed_purpose_round_trip() {
+        let ct = CookieToken::new();
+        let s = ct.gen_purpose_token::<Checkout>().to_string();
+        ct.verify_purpose_token(PurposeToken::<Checkout>::try_from(s.as_str()).unwrap())
+            .unwrap();
+        assert_eq!(
+            ct.verify_purpose_token(PurposeToken::<PasswordChange>::try_from(s.as_str()).unwrap()),
+            Err(Error::WrongPurpose)
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! The "refresh my token" endpoint every SPA eventually needs.
+//!
+//! Apps using a request token per request can instead hand out the next
+//! token on every response, with [`refresh_header`]. The client contract is:
+//!
+//! - After each successful unsafe request, the response carries a fresh
+//!   request token in the header the `Extractor` reads tokens from,
+//!   `X-XSRF-Token` by default. The client sends it back in the same header
+//!   with its next unsafe request.
+//! - Responses to safe requests, and failed ones, don't carry the header,
+//!   and the client keeps using the token it has.
+//! - Cross-origin clients need the header listed in
+//!   `Access-Control-Expose-Headers` to be able to read it.
+use crate::json::Object;
+use crate::{
+    CookieError, CookieToken, CsrfProtection, Extractor, Policy, Request, RequestToken,
+    TokenCookie, Verified,
+};
+use std::convert::TryFrom;
+
+/// The response of a token refresh endpoint.
+///
+/// The body is a JSON object with the request token as the `token` member.
+pub struct Refresh {
+    /// The current cookie token, which is new if `rotated` is set.
+    pub cookie_token: CookieToken,
+    /// Whether the cookie token was replaced, in which case `headers`
+    /// includes a `Set-Cookie` header for it.
+    pub rotated: bool,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Issue a new request token from the current cookie token, given the raw
+/// value of its cookie. A missing or invalid cookie token is replaced with a
+/// new one, failing if the cookie is misconfigured.
+pub fn refresh(
+    protection: &dyn CsrfProtection,
+    current: Option<&str>,
+    cookie: &TokenCookie,
+) -> Result<Refresh, CookieError> {
+    let mut headers = vec![
+        ("Cache-Control".to_owned(), "no-store".to_owned()),
+        ("Content-Type".to_owned(), "application/json".to_owned()),
+    ];
+    let (cookie_token, rotated) = match current.and_then(|v| CookieToken::try_from(v).ok()) {
+        Some(ct) => (ct, false),
+        None => {
+            let ct = protection.issue_cookie_token();
+            headers.push((
+                "Set-Cookie".to_owned(),
+                cookie.set_cookie_header(&ct.to_string())?,
+            ));
+            (ct, true)
+        }
+    };
+    let token = protection.issue_req_token(&cookie_token).to_string();
+    Ok(Refresh {
+        cookie_token,
+        rotated,
+        headers,
+        body: Object::new().str("token", &token).finish(),
+    })
+}
+
+/// The header carrying a fresh request token from `cookie`, to add to the
+/// response once `req` was verified, if `policy` required it to carry a
+/// token. `verified` is the token `req` carried, as proof of that. The header
+/// is the one `extractor` reads tokens from, so clients can echo it back
+/// as is. Integrations call this after running the handler, so failed
+/// requests don't rotate the client's token.
+pub fn refresh_header<R: Request + ?Sized>(
+    req: &R,
+    policy: &Policy,
+    extractor: &Extractor,
+    protection: &dyn CsrfProtection,
+    cookie: &CookieToken,
+    _verified: &Verified<RequestToken>,
+) -> Option<(String, String)> {
+    if !policy.requires_token(req) {
+        return None;
+    }
+    let token = protection.issue_req_token(cookie).to_string();
+    Some((extractor.header().to_owned(), token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{refresh, refresh_header};
+    use crate::request::tests::TestRequest;
+    use crate::{
+        CookieToken, CsrfProtection, Extractor, Plain, Policy, RequestToken, Source, TokenCookie,
+    };
+    use std::convert::TryInto;
+
+    fn token(body: &str) -> RequestToken {
+        body[r#"{"token":""#.len()..body.len() - 2]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn keeps_valid_cookie_token() {
+        let ct = CookieToken::new().to_string();
+        let r = refresh(&Plain, Some(&ct), &TokenCookie::default()).unwrap();
+        assert!(!r.rotated);
+        assert!(!r.headers.iter().any(|(n, _)| n == "Set-Cookie"));
+        assert_eq!(r.cookie_token.to_string(), ct);
+        r.cookie_token.verify_req_token(token(&r.body)).unwrap();
+    }
+
+    #[test]
+    fn rotates_invalid_cookie_token() {
+        let r = refresh(&Plain, Some("garbage"), &TokenCookie::default()).unwrap();
+        assert!(r.rotated);
+        assert!(r.headers.iter().any(|(n, _)| n == "Set-Cookie"));
+        r.cookie_token.verify_req_token(token(&r.body)).unwrap();
+    }
+
+    #[test]
+    fn refresh_header_after_unsafe_requests() {
+        let ct = CookieToken::new();
+        let policy = Policy::new();
+        let e = Extractor::new();
+        let verified = Plain.verified(&ct, ct.gen_req_token()).unwrap();
+        let get = TestRequest::new("GET", "/");
+        assert_eq!(
+            refresh_header(&get, &policy, &e, &Plain, &ct, &verified),
+            None
+        );
+        let post = TestRequest::new("POST", "/");
+        let (name, value) = refresh_header(&post, &policy, &e, &Plain, &ct, &verified).unwrap();
+        assert_eq!(name, "X-XSRF-Token");
+        ct.verify_req_token(value.as_str().try_into().unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn clients_echo_the_refresh_header() {
+        let ct = CookieToken::new();
+        let policy = Policy::new();
+        let e = Extractor::new().with_header("X-Next-Token");
+        let mut token = ct.gen_req_token().to_string();
+        for _ in 0..3 {
+            let req = TestRequest::new("POST", "/").header("X-Next-Token", &token);
+            let (source, verified) = e.verify_any(&req, b"", &Plain, &ct).unwrap();
+            assert_eq!(source, Source::Header);
+            let (name, value) = refresh_header(&req, &policy, &e, &Plain, &ct, &verified).unwrap();
+            assert_eq!(name, "X-Next-Token");
+            token = value;
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Turning verification failures into HTTP responses.
+//!
+//! A [`Rejection`] is a framework neutral description of the response, which
+//! integrations convert into the response type of their framework. Sharing
+//! it keeps the status codes and headers consistent between integrations.
+use crate::json::Object;
+use crate::{CookieError, CsrfProtection, Error, Request, TokenCookie};
+
+/// The status used for expired tokens, popularized by Laravel.
+pub const STATUS_EXPIRED: u16 = 419;
+
+/// A response rejecting a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Rejection {
+    /// The HTTP status for an `Error`.
+    pub fn status_for(err: Error) -> u16 {
+        match err {
+            Error::InvalidToken => 400,
+            Error::Expired => STATUS_EXPIRED,
+            Error::ContentType => 415,
+            Error::TokenMismatch
+            | Error::WrongPurpose
+            | Error::Replayed
+            | Error::WrongScope
+            | Error::WrongEnvironment
+            | Error::Revoked
+            | Error::Honeypot => 403,
+        }
+    }
+
+    /// A `303 See Other` redirect, for example to a login page.
+    pub fn redirect(location: &str) -> Rejection {
+        Self {
+            status: 303,
+            headers: vec![
+                ("Cache-Control".to_owned(), "no-store".to_owned()),
+                ("Location".to_owned(), location.to_owned()),
+            ],
+            body: String::new(),
+        }
+    }
+
+    /// Attach a fresh `CookieToken` and `RequestToken` to the rejection, so
+    /// clients can transparently retry the request. The cookie token is set
+    /// using `cookie`, and the request token is sent in the `header`.
+    ///
+    /// This replaces the client's cookie token, invalidating other request
+    /// tokens it holds, so it is opt-in. Fails if the cookie is
+    /// misconfigured.
+    pub fn reissue(
+        mut self,
+        protection: &dyn CsrfProtection,
+        cookie: &TokenCookie,
+        header: &str,
+    ) -> std::result::Result<Rejection, CookieError> {
+        let ct = protection.issue_cookie_token();
+        let rt = protection.issue_req_token(&ct);
+        self.headers.push((
+            "Set-Cookie".to_owned(),
+            cookie.set_cookie_header(&ct.to_string())?,
+        ));
+        self.headers.push((header.to_owned(), rt.to_string()));
+        Ok(self)
+    }
+}
+
+/// Maps verification failures to responses.
+///
+/// Integrations accept a `RejectionHandler` trait object, allowing users to
+/// redirect, render a friendly error page or return JSON instead of the
+/// default plain text response. Closures taking the request and the error
+/// are also handlers.
+pub trait RejectionHandler: Send + Sync {
+    fn handle(&self, req: &dyn Request, err: Error) -> Rejection;
+}
+
+/// The default handler, responding with `Rejection::from`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRejectionHandler;
+
+impl RejectionHandler for DefaultRejectionHandler {
+    fn handle(&self, _req: &dyn Request, err: Error) -> Rejection {
+        Rejection::from(err)
+    }
+}
+
+impl<F> RejectionHandler for F
+where
+    F: Fn(&dyn Request, Error) -> Rejection + Send + Sync,
+{
+    fn handle(&self, req: &dyn Request, err: Error) -> Rejection {
+        self(req, err)
+    }
+}
+
+impl From<Error> for Rejection {
+    /// A plain text response with a generic body, which never reveals more
+    /// than the status does.
+    fn from(err: Error) -> Self {
+        let status = Rejection::status_for(err);
+        let body = match status {
+            400 => "Bad Request",
+            STATUS_EXPIRED => "Page Expired",
+            _ => "Forbidden",
+        };
+        Self {
+            status,
+            headers: vec![
+                ("Cache-Control".to_owned(), "no-store".to_owned()),
+                (
+                    "Content-Type".to_owned(),
+                    "text/plain; charset=utf-8".to_owned(),
+                ),
+            ],
+            body: body.to_owned(),
+        }
+    }
+}
+
+/// Builds `application/problem+json` rejections, as described in RFC 7807,
+/// for JSON APIs whose clients expect structured errors.
+///
+/// The body includes the stable `Error::code` as the `code` member.
+#[derive(Clone, Debug)]
+pub struct Problem {
+    err: Error,
+    type_uri: String,
+    title: Option<String>,
+    detail: Option<String>,
+    refresh: Option<String>,
+}
+
+impl Problem {
+    pub fn new(err: Error) -> Problem {
+        Self {
+            err,
+            type_uri: "about:blank".to_owned(),
+            title: None,
+            detail: None,
+            refresh: None,
+        }
+    }
+
+    /// A URI identifying the problem type. Defaults to `about:blank`.
+    pub fn with_type(mut self, type_uri: &str) -> Problem {
+        self.type_uri = type_uri.to_owned();
+        self
+    }
+
+    /// A short summary of the problem type. Defaults to the generic body of
+    /// the plain text rejection.
+    pub fn with_title(mut self, title: &str) -> Problem {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    /// An explanation specific to this occurrence.
+    pub fn with_detail(mut self, detail: &str) -> Problem {
+        self.detail = Some(detail.to_owned());
+        self
+    }
+
+    /// Where the client can obtain a fresh token before retrying, added as
+    /// the `refresh` member.
+    pub fn with_refresh(mut self, url: &str) -> Problem {
+        self.refresh = Some(url.to_owned());
+        self
+    }
+
+    pub fn into_rejection(self) -> Rejection {
+        let mut rejection = Rejection::from(self.err);
+        let title = self.title.unwrap_or(rejection.body);
+        let mut body = Object::new()
+            .str("type", &self.type_uri)
+            .str("title", &title)
+            .raw("status", &rejection.status.to_string())
+            .str("code", self.err.code());
+        if let Some(detail) = &self.detail {
+            body = body.str("detail", detail);
+        }
+        if let Some(refresh) = &self.refresh {
+            body = body.str("refresh", refresh);
+        }
+        rejection.body = body.finish();
+        for (name, value) in &mut rejection.headers {
+            if name == "Content-Type" {
+                *value = "application/problem+json".to_owned();
+            }
+        }
+        rejection
+    }
+}
+
+impl From<Problem> for Rejection {
+    fn from(p: Problem) -> Self {
+        p.into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DefaultRejectionHandler, Problem, Rejection, RejectionHandler};
+    use crate::request::tests::TestRequest;
+    use crate::{CookieToken, Error, Plain, Request, RequestToken, TokenCookie};
+    use std::convert::TryInto;
+
+    #[test]
+    fn maps_errors() {
+        assert_eq!(Rejection::from(Error::TokenMismatch).status, 403);
+        assert_eq!(Rejection::from(Error::InvalidToken).status, 400);
+        let r = Rejection::from(Error::Expired);
+        assert_eq!(r.status, 419);
+        assert!(r
+            .headers
+            .contains(&("Cache-Control".to_owned(), "no-store".to_owned())));
+    }
+
+    #[test]
+    fn problem_json() {
+        let r = Problem::new(Error::Expired)
+            .with_detail("The form was open too long.")
+            .with_refresh("/xsrf/refresh")
+            .into_rejection();
+        assert_eq!(r.status, 419);
+        assert!(r.headers.contains(&(
+            "Content-Type".to_owned(),
+            "application/problem+json".to_owned()
+        )));
+        assert_eq!(
+            r.body,
+            r#"{"type":"about:blank","title":"Page Expired","status":419,"code":"xsrf.expired","detail":"The form was open too long.","refresh":"/xsrf/refresh"}"#
+        );
+    }
+
+    #[test]
+    fn rejection_handlers() {
+        let req = TestRequest::new("POST", "/settings");
+        let handlers: Vec<Box<dyn RejectionHandler>> = vec![
+            Box::new(DefaultRejectionHandler),
+            Box::new(|req: &dyn Request, err| match err {
+                Error::Expired => Rejection::redirect(req.path()),
+                err => Rejection::from(err),
+            }),
+        ];
+        assert_eq!(handlers[0].handle(&req, Error::Expired).status, 419);
+        let r = handlers[1].handle(&req, Error::Expired);
+        assert_eq!(r.status, 303);
+        assert!(r
+            .headers
+            .contains(&("Location".to_owned(), "/settings".to_owned())));
+    }
+
+    #[test]
+    fn reissue_attaches_fresh_tokens() {
+        let r = Rejection::from(Error::Expired)
+            .reissue(&Plain, &TokenCookie::default(), "X-XSRF-Token")
+            .unwrap();
+        let header = |name: &str| {
+            r.headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        let set_cookie = header("Set-Cookie");
+        let value = &set_cookie["xsrf=".len()..set_cookie.find(';').unwrap()];
+        let ct: CookieToken = value.try_into().unwrap();
+        let rt: RequestToken = header("X-XSRF-Token").as_str().try_into().unwrap();
+        ct.verify_req_token(rt).unwrap();
+    }
+}
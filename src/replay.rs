@@ -0,0 +1,91 @@
+//! Replay protection for one-time request tokens.
+//!
+//! Every request token carries a random one-time-pad, which doubles as its
+//! nonce. When an `XsrfConfig` has a `ReplayStore`, each successfully
+//! verified token has its nonce recorded, and tokens whose nonce was already
+//! recorded are rejected with `Error::Replayed`.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+mod asynchronous;
+mod bloom;
+mod sharded;
+
+pub use asynchronous::{AsyncReplayStore, BoxFuture};
+pub use bloom::BloomReplayStore;
+pub use sharded::ShardedReplayStore;
+
+/// The nonce of a request token.
+pub type Nonce = [u8; 32];
+
+/// Records the nonces of used request tokens.
+pub trait ReplayStore: std::fmt::Debug + Send + Sync {
+    /// Record `nonce` as used at `now`, in seconds since the unix epoch.
+    /// Returns `false` if it had already been recorded.
+    fn insert(&self, nonce: Nonce, now: u64) -> bool;
+}
+
+/// An exact in-memory replay store.
+///
+/// Nonces are forgotten once they are older than the retention period, which
+/// should be at least as long as the TTL of the tokens.
+#[derive(Debug)]
+pub struct MemoryReplayStore {
+    retention: u64,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    nonces: HashMap<Nonce, u64>,
+    last_pruned: u64,
+}
+
+impl MemoryReplayStore {
+    pub fn new(retention: Duration) -> MemoryReplayStore {
+        Self {
+            retention: retention.as_secs(),
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn insert(&self, nonce: Nonce, now: u64) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.insert(nonce, now, self.retention)
+    }
+}
+
+impl Seen {
+    fn insert(&mut self, nonce: Nonce, now: u64, retention: u64) -> bool {
+        if now > self.last_pruned {
+            let cutoff = now.saturating_sub(retention);
+            self.nonces.retain(|_, at| *at >= cutoff);
+            self.last_pruned = now;
+        }
+        match self.nonces.get(&nonce) {
+            Some(at) if now.saturating_sub(*at) <= retention => false,
+            _ => {
+                self.nonces.insert(nonce, now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryReplayStore, ReplayStore};
+    use std::time::Duration;
+
+    #[test]
+    fn memory_store_rejects_replay() {
+        let store = MemoryReplayStore::new(Duration::from_secs(60));
+        assert!(store.insert([1; 32], 100));
+        assert!(!store.insert([1; 32], 150));
+        assert!(store.insert([2; 32], 150));
+        assert!(store.insert([1; 32], 161));
+    }
+}
@@ -0,0 +1,84 @@
+//! Async variants of the replay store, for stores backed by the network.
+use super::{Nonce, ReplayStore};
+use crate::config::{use_nonces, XsrfConfig};
+use crate::{CookieToken, RequestToken, Result, VerifyOutcome};
+use std::future::Future;
+use std::pin::Pin;
+
+/// A boxed future, as returned by the async store traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An async `ReplayStore`, for implementations backed by Redis, Postgres or
+/// similar which shouldn't block the executor.
+///
+/// Every `ReplayStore` is also an `AsyncReplayStore`.
+pub trait AsyncReplayStore: Send + Sync {
+    /// Record `nonce` as used at `now`, in seconds since the unix epoch.
+    /// Resolves to `false` if it had already been recorded.
+    fn insert(&self, nonce: Nonce, now: u64) -> BoxFuture<'_, bool>;
+}
+
+impl<S: ReplayStore + ?Sized> AsyncReplayStore for S {
+    fn insert(&self, nonce: Nonce, now: u64) -> BoxFuture<'_, bool> {
+        let fresh = ReplayStore::insert(self, nonce, now);
+        Box::pin(std::future::ready(fresh))
+    }
+}
+
+impl CookieToken {
+    /// Verify the token like `verify_req_token_with`, and additionally
+    /// record it as used in the given async store.
+    ///
+    /// The config should not also have a replay store, since it would be
+    /// consulted first, blocking the executor.
+    pub async fn verify_req_token_async(
+        &self,
+        token: RequestToken,
+        config: &XsrfConfig,
+        store: &dyn AsyncReplayStore,
+    ) -> Result<()> {
+        let mut outcome = config.evaluate(self, &token, None);
+        if outcome.is_valid() {
+            outcome = VerifyOutcome::Replayed;
+            for nonce in use_nonces(&token) {
+                if store.insert(nonce, config.now()).await {
+                    outcome = VerifyOutcome::Valid;
+                    break;
+                }
+            }
+        }
+        config.audit_outcome(self, outcome);
+        outcome.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CookieToken, Error, MemoryReplayStore, XsrfConfig};
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = Box::pin(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    #[test]
+    fn async_store_rejects_replay() {
+        let store = MemoryReplayStore::new(Duration::from_secs(60));
+        let config = XsrfConfig::new();
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token();
+        block_on(ct.verify_req_token_async(rt.clone(), &config, &store)).unwrap();
+        assert_eq!(
+            block_on(ct.verify_req_token_async(rt, &config, &store)),
+            Err(Error::Replayed)
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! A probabilistic replay store, for sites that can not afford an exact set
+//! of nonces.
+use super::{Nonce, ReplayStore};
+use crate::sha256::Sha256;
+use rand::{thread_rng, Rng};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MAX_HASHES: usize = 8;
+
+/// A replay store backed by two rotating bloom filters.
+///
+/// Nonces are recorded in the current filter, and checked against both the
+/// current and the previous one. The filters rotate every window, so a nonce
+/// is remembered for at least one and at most two windows. The window should
+/// be at least as long as the TTL of the tokens.
+///
+/// False positives cause legitimate tokens to be rejected as replayed, at
+/// roughly the configured rate once a window holds the expected number of
+/// nonces. There are never false negatives.
+#[derive(Debug)]
+pub struct BloomReplayStore {
+    key: [u8; 32],
+    hashes: usize,
+    window: u64,
+    filters: Mutex<Filters>,
+}
+
+#[derive(Debug)]
+struct Filters {
+    started: u64,
+    current: Vec<u64>,
+    previous: Vec<u64>,
+}
+
+impl BloomReplayStore {
+    /// Create a store sized for `expected` nonces per window with the given
+    /// false positive rate.
+    pub fn new(expected: usize, false_positive_rate: f64, window: Duration) -> BloomReplayStore {
+        let n = expected.max(1) as f64;
+        let p = false_positive_rate.clamp(1e-12, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * p.ln() / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let words = bits.div_ceil(64);
+        let hashes = ((words * 64) as f64 / n * ln2).round() as usize;
+        let mut key = [0; 32];
+        thread_rng().fill(&mut key);
+        Self {
+            key,
+            hashes: hashes.clamp(1, MAX_HASHES),
+            window: window.as_secs().max(1),
+            filters: Mutex::new(Filters {
+                started: 0,
+                current: vec![0; words],
+                previous: vec![0; words],
+            }),
+        }
+    }
+
+    /// The bit positions for a nonce, derived with a per-store random key so
+    /// they can't be predicted from the outside.
+    fn positions(&self, nonce: &Nonce, bits: usize) -> [usize; MAX_HASHES] {
+        let mut h = Sha256::new();
+        h.update(&self.key);
+        h.update(nonce);
+        let digest = h.finish();
+        let mut positions = [0; MAX_HASHES];
+        for (p, c) in positions.iter_mut().zip(digest.chunks(4)) {
+            *p = u32::from_be_bytes([c[0], c[1], c[2], c[3]]) as usize % bits;
+        }
+        positions
+    }
+}
+
+impl ReplayStore for BloomReplayStore {
+    fn insert(&self, nonce: Nonce, now: u64) -> bool {
+        let mut f = self.filters.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_sub(f.started);
+        if elapsed >= self.window * 2 {
+            f.current.iter_mut().for_each(|w| *w = 0);
+            f.previous.iter_mut().for_each(|w| *w = 0);
+            f.started = now;
+        } else if elapsed >= self.window {
+            let Filters {
+                current, previous, ..
+            } = &mut *f;
+            std::mem::swap(current, previous);
+            current.iter_mut().for_each(|w| *w = 0);
+            f.started += self.window;
+        }
+        let positions = self.positions(&nonce, f.current.len() * 64);
+        let positions = &positions[..self.hashes];
+        let contains = |filter: &[u64]| {
+            positions
+                .iter()
+                .all(|&p| filter[p / 64] & (1 << (p % 64)) != 0)
+        };
+        if contains(&f.current) || contains(&f.previous) {
+            return false;
+        }
+        positions
+            .iter()
+            .for_each(|&p| f.current[p / 64] |= 1 << (p % 64));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomReplayStore;
+    use crate::ReplayStore;
+    use std::time::Duration;
+
+    #[test]
+    fn bloom_store_rejects_replay() {
+        let store = BloomReplayStore::new(1000, 0.001, Duration::from_secs(60));
+        assert!(store.insert([1; 32], 100));
+        assert!(!store.insert([1; 32], 101));
+        assert!(store.insert([2; 32], 101));
+    }
+
+    #[test]
+    fn bloom_store_rotates() {
+        let store = BloomReplayStore::new(1000, 0.001, Duration::from_secs(60));
+        assert!(store.insert([1; 32], 0));
+        assert!(!store.insert([1; 32], 60));
+        assert!(!store.insert([1; 32], 119));
+        assert!(store.insert([1; 32], 240));
+    }
+}
@@ -0,0 +1,73 @@
+//! A minimal view of an HTTP request.
+//!
+//! The crate does not depend on any HTTP stack. Integrations implement
+//! [`Request`] for the request type of their framework, which allows the
+//! request level helpers in this crate to be shared between them.
+
+/// The parts of an HTTP request the request level helpers need.
+pub trait Request {
+    /// The request method, such as `POST`.
+    fn method(&self) -> &str;
+
+    /// The request path, without the query string.
+    fn path(&self) -> &str;
+
+    /// The value of the first header named `name`, compared
+    /// case-insensitively.
+    fn header(&self, name: &str) -> Option<&str>;
+}
+
+/// The media type of a `Content-Type` value, without parameters, in lower
+/// case.
+pub(crate) fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::Request;
+
+    /// A request for tests.
+    pub(crate) struct TestRequest {
+        pub(crate) method: &'static str,
+        pub(crate) path: &'static str,
+        pub(crate) headers: Vec<(&'static str, String)>,
+    }
+
+    impl TestRequest {
+        pub(crate) fn new(method: &'static str, path: &'static str) -> TestRequest {
+            Self {
+                method,
+                path,
+                headers: Vec::new(),
+            }
+        }
+
+        pub(crate) fn header(mut self, name: &'static str, value: &str) -> TestRequest {
+            self.headers.push((name, value.to_owned()));
+            self
+        }
+    }
+
+    impl Request for TestRequest {
+        fn method(&self) -> &str {
+            self.method
+        }
+
+        fn path(&self) -> &str {
+            self.path
+        }
+
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Request tokens scoped to a single request.
+//!
+//! Rendering a page may ask for the request token many times, for example
+//! once per form partial. Middleware places a [`TokenScope`] in the request
+//! extensions, and every caller gets the same token instead of a new one
+//! being generated each time.
+//!
+//! Issuance is also lazy. A request without a `CookieToken` only gets one
+//! when a handler actually asks for a token, so purely static `GET` traffic
+//! does not get a `Set-Cookie`, which would otherwise defeat caching.
+use crate::{CookieToken, CsrfProtection};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Generates at most one `RequestToken` per request, and a `CookieToken`
+/// only if needed.
+pub struct TokenScope {
+    protection: Arc<dyn CsrfProtection>,
+    existing: Option<CookieToken>,
+    issued: OnceLock<CookieToken>,
+    token: OnceLock<String>,
+}
+
+impl TokenScope {
+    /// Create the scope for a request, with the `CookieToken` it carried if
+    /// any.
+    pub fn new(protection: Arc<dyn CsrfProtection>, cookie: Option<CookieToken>) -> TokenScope {
+        Self {
+            protection,
+            existing: cookie,
+            issued: OnceLock::new(),
+            token: OnceLock::new(),
+        }
+    }
+
+    /// The encoded request token for this request, generated on first use.
+    pub fn token(&self) -> &str {
+        self.token.get_or_init(|| {
+            self.protection
+                .issue_req_token(self.cookie_token())
+                .to_string()
+        })
+    }
+
+    /// The `CookieToken` request tokens are derived from, issuing one if the
+    /// request did not carry it.
+    pub fn cookie_token(&self) -> &CookieToken {
+        match &self.existing {
+            Some(ct) => ct,
+            None => self
+                .issued
+                .get_or_init(|| self.protection.issue_cookie_token()),
+        }
+    }
+
+    /// The `CookieToken` issued during this request, if any. Middleware sets
+    /// the cookie only when this is `Some`.
+    pub fn issued_cookie_token(&self) -> Option<&CookieToken> {
+        self.issued.get()
+    }
+}
+
+impl fmt::Debug for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenScope")
+            .field("issued", &self.issued.get().is_some())
+            .field("token", &self.token.get().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenScope;
+    use crate::{CookieToken, Plain, RequestToken};
+    use std::convert::TryFrom;
+    use std::sync::Arc;
+
+    #[test]
+    fn token_is_generated_once() {
+        let ct = CookieToken::new();
+        let encoded = ct.to_string();
+        let scope = TokenScope::new(Arc::new(Plain), Some(ct));
+        let first = scope.token().to_owned();
+        assert_eq!(scope.token(), first);
+        assert!(scope.issued_cookie_token().is_none());
+        let ct = CookieToken::try_from(encoded.as_str()).unwrap();
+        ct.verify_req_token(RequestToken::try_from(first.as_str()).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn cookie_token_is_issued_lazily() {
+        let scope = TokenScope::new(Arc::new(Plain), None);
+        assert!(scope.issued_cookie_token().is_none());
+        let rt = RequestToken::try_from(scope.token()).unwrap();
+        let ct = scope.issued_cookie_token().unwrap();
+        assert_eq!(ct.as_bytes(), scope.cookie_token().as_bytes());
+        ct.verify_req_token(rt).unwrap();
+    }
+}
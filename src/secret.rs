@@ -0,0 +1,115 @@
+//! Deriving all keys from a single master secret.
+//!
+//! Operators provide one high entropy secret, and distinct sub-keys for each
+//! use are derived from it with HKDF-SHA-256, using a labelled info string
+//! per use. Compromise of one sub-key does not reveal the others.
+use crate::key::zeroize;
+use crate::sha256::{hkdf_expand, hkdf_extract};
+use crate::{Key, MIN_KEY_LEN};
+use std::path::Path;
+
+const SALT: &[u8] = b"xsrf secret v1";
+
+/// Errors loading a master secret.
+#[derive(thiserror::Error, Debug)]
+pub enum SecretError {
+    #[error("xsrf secret environment variable {0} is not set")]
+    MissingVar(String),
+    #[error("xsrf secret could not be read: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("xsrf secret is not valid base64")]
+    InvalidEncoding,
+    #[error("xsrf secret must be at least 32 bytes")]
+    TooShort,
+}
+
+/// A master secret from which sub-keys are derived. Its `Debug` output is
+/// redacted.
+#[derive(Clone, Debug)]
+pub struct Secret {
+    prk: Key,
+}
+
+impl Secret {
+    /// Use the given bytes as the master secret. They must be at least
+    /// `MIN_KEY_LEN` bytes of high entropy data.
+    pub fn new(bytes: &[u8]) -> Result<Secret, SecretError> {
+        if bytes.len() < MIN_KEY_LEN {
+            return Err(SecretError::TooShort);
+        }
+        Ok(Self {
+            prk: Key::new(&hkdf_extract(SALT, bytes)),
+        })
+    }
+
+    /// Load the master secret from the named environment variable, holding
+    /// it as URL safe base64.
+    pub fn from_env(var: &str) -> Result<Secret, SecretError> {
+        let value = std::env::var(var).map_err(|_| SecretError::MissingVar(var.to_owned()))?;
+        Self::from_base64(&value)
+    }
+
+    /// Load the master secret from a file, holding it as URL safe base64.
+    /// Surrounding whitespace, such as a trailing newline, is ignored.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Secret, SecretError> {
+        Self::from_base64(&std::fs::read_to_string(path)?)
+    }
+
+    fn from_base64(value: &str) -> Result<Secret, SecretError> {
+        let mut bytes = base64::decode_config(value.trim(), base64::URL_SAFE)
+            .map_err(|_| SecretError::InvalidEncoding)?;
+        let secret = Self::new(&bytes);
+        zeroize(&mut bytes);
+        secret
+    }
+
+    /// Derive the sub-key for `label`. Distinct labels give independent
+    /// keys, and the same label always gives the same key.
+    pub fn derive(&self, label: &str) -> Key {
+        let mut out = [0; MIN_KEY_LEN];
+        hkdf_expand(self.prk.as_bytes(), label.as_bytes(), &mut out);
+        let key = Key::new(&out);
+        zeroize(&mut out);
+        key
+    }
+
+    /// The sub-key for signing cookies.
+    pub fn cookie_signing_key(&self) -> Key {
+        self.derive("xsrf cookie signing")
+    }
+
+    /// The sub-key for token HMACs, such as with `SignedDoubleSubmit`.
+    pub fn token_key(&self) -> Key {
+        self.derive("xsrf token hmac")
+    }
+
+    /// The sub-key for encryption.
+    pub fn encryption_key(&self) -> Key {
+        self.derive("xsrf encryption")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Secret, SecretError};
+
+    #[test]
+    fn sub_keys_are_distinct_and_stable() {
+        let secret = Secret::new(&[7; 32]).unwrap();
+        let again = Secret::from_base64(&base64::encode_config([7; 32], base64::URL_SAFE)).unwrap();
+        assert_eq!(secret.token_key().as_bytes(), again.token_key().as_bytes());
+        assert_ne!(
+            secret.token_key().as_bytes(),
+            secret.cookie_signing_key().as_bytes()
+        );
+        assert_ne!(
+            secret.token_key().as_bytes(),
+            secret.encryption_key().as_bytes()
+        );
+        assert!(matches!(Secret::new(&[7; 31]), Err(SecretError::TooShort)));
+        assert!(matches!(
+            Secret::from_env("XSRF_TEST_SECRET_UNSET"),
+            Err(SecretError::MissingVar(_))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! Storing the `CookieToken` in a server side session.
+//!
+//! This is the synchronizer token pattern: the `CookieToken` never leaves the
+//! server, and only `RequestToken`s are sent to the client. Session
+//! integrations implement [`Session`] for their session type, and get the
+//! token lifecycle for free.
+//!
+//! Login forms need protection too, or an attacker can log the victim into
+//! the attacker's account. Sessions therefore hold a token before
+//! authentication: render the login form with a token from `csrf_token` on
+//! the anonymous session, and on submission call `verify_login_csrf`, which
+//! verifies it and rotates the token for the authenticated session.
+use crate::{CookieToken, Error, RequestToken, Result};
+use std::convert::TryFrom;
+
+/// The session key the token is stored under.
+pub const SESSION_KEY: &str = "xsrf";
+
+/// A server side session storing string values.
+pub trait Session {
+    fn get(&self, key: &str) -> Option<String>;
+
+    fn insert(&mut self, key: &str, value: String);
+
+    /// The `CookieToken` stored in the session, storing a new one if there
+    /// is none.
+    fn csrf_token(&mut self) -> CookieToken {
+        match self
+            .get(SESSION_KEY)
+            .and_then(|v| CookieToken::try_from(v.as_str()).ok())
+        {
+            Some(ct) => ct,
+            None => self.rotate_csrf_token(),
+        }
+    }
+
+    /// Replace the `CookieToken` stored in the session. This should be done
+    /// whenever the authentication state changes, such as on login, to
+    /// prevent token fixation.
+    fn rotate_csrf_token(&mut self) -> CookieToken {
+        let ct = CookieToken::new();
+        self.insert(SESSION_KEY, ct.to_string());
+        ct
+    }
+
+    /// Verify a `RequestToken` against the `CookieToken` stored in the
+    /// session. A session without a token never verifies.
+    fn verify_csrf(&self, token: RequestToken) -> Result<()> {
+        let ct = self.get(SESSION_KEY).ok_or(Error::TokenMismatch)?;
+        CookieToken::try_from(ct.as_str())?.verify_req_token(token)
+    }
+
+    /// Verify the `RequestToken` submitted with a login form against the
+    /// pre-session token, and on success rotate it, returning the token of
+    /// the authenticated session. Call this before marking the session as
+    /// authenticated.
+    fn verify_login_csrf(&mut self, token: RequestToken) -> Result<CookieToken> {
+        self.verify_csrf(token)?;
+        Ok(self.rotate_csrf_token())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::Session;
+    use crate::Error;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    pub(crate) struct TestSession(pub(crate) HashMap<String, String>);
+
+    impl Session for TestSession {
+        fn get(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn insert(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+    }
+
+    #[test]
+    fn session_token_lifecycle() {
+        let mut session = TestSession::default();
+        assert_eq!(
+            session.verify_csrf(crate::CookieToken::new().gen_req_token()),
+            Err(Error::TokenMismatch)
+        );
+        let rt = session.csrf_token().gen_req_token();
+        session.verify_csrf(rt.clone()).unwrap();
+        let fresh = session.csrf_token().gen_req_token();
+        session.verify_csrf(fresh).unwrap();
+        session.rotate_csrf_token();
+        assert_eq!(session.verify_csrf(rt), Err(Error::TokenMismatch));
+    }
+
+    #[test]
+    fn login_rotates_pre_session_token() {
+        let mut session = TestSession::default();
+        let login = session.csrf_token().gen_req_token();
+        let authenticated = session.verify_login_csrf(login.clone()).unwrap();
+        assert_eq!(
+            session.verify_login_csrf(login).err(),
+            Some(Error::TokenMismatch)
+        );
+        session.verify_csrf(authenticated.gen_req_token()).unwrap();
+    }
+}
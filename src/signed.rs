@@ -0,0 +1,117 @@
+//! The signed double submit cookie strategy recommended by OWASP.
+//!
+//! With the plain strategy, an attacker able to plant a cookie on the victim,
+//! for example from a sibling sub-domain, can plant their own `CookieToken`
+//! and submit matching `RequestToken`s. This strategy instead derives request
+//! tokens from `HMAC(key, session_id || cookie_token)`, binding them to the
+//! session. Without the key, a planted cookie is of no use.
+use crate::sha256::hmac;
+use crate::{CookieToken, CsrfProtection, Key, RequestToken, Result, XsrfConfig};
+
+const DOMAIN: &[u8] = b"xsrf signed double submit\0";
+
+/// Issues and verifies tokens bound to a session.
+///
+/// This is constructed per request, for the session the request belongs to.
+#[derive(Clone, Debug)]
+pub struct SignedDoubleSubmit {
+    key: Key,
+    session_id: Vec<u8>,
+    config: XsrfConfig,
+}
+
+impl SignedDoubleSubmit {
+    pub fn for_session(key: &Key, session_id: &[u8]) -> SignedDoubleSubmit {
+        Self {
+            key: key.clone(),
+            session_id: session_id.to_vec(),
+            config: XsrfConfig::default(),
+        }
+    }
+
+    /// Issue and verify request tokens with the given config.
+    pub fn with_config(mut self, config: XsrfConfig) -> SignedDoubleSubmit {
+        self.config = config;
+        self
+    }
+
+    /// The cookie token bound to the session, from which request tokens are
+    /// derived.
+    fn bind(&self, cookie: &CookieToken) -> CookieToken {
+        let len = (self.session_id.len() as u64).to_be_bytes();
+        cookie.derive_child(hmac(
+            self.key.as_bytes(),
+            &[DOMAIN, &len, &self.session_id, cookie.as_bytes()],
+        ))
+    }
+}
+
+impl CsrfProtection for SignedDoubleSubmit {
+    fn issue_req_token(&self, cookie: &CookieToken) -> RequestToken {
+        self.bind(cookie).gen_req_token_with(&self.config)
+    }
+
+    fn verify(&self, cookie: &CookieToken, token: RequestToken) -> Result<()> {
+        self.bind(cookie).verify_req_token_with(token, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignedDoubleSubmit;
+    use crate::{
+        AuditEvent, CookieToken, CsrfProtection, Error, Key, MemoryAuditSink,
+        MemoryRevocationStore, TokenFamily, VerifyOutcome, XsrfConfig,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn bound_to_session_and_key() {
+        let key = Key::generate();
+        let p = SignedDoubleSubmit::for_session(&key, b"session-a");
+        let ct = p.issue_cookie_token();
+        p.verify(&ct, p.issue_req_token(&ct)).unwrap();
+
+        // a pair minted for another session does not verify
+        let other = SignedDoubleSubmit::for_session(&key, b"session-b");
+        assert_eq!(
+            p.verify(&ct, other.issue_req_token(&ct)),
+            Err(Error::TokenMismatch)
+        );
+
+        // an attacker planting a cookie can't mint tokens without the key
+        let planted = CookieToken::new();
+        assert_eq!(
+            p.verify(&planted, planted.gen_req_token()),
+            Err(Error::TokenMismatch)
+        );
+        let attacker = SignedDoubleSubmit::for_session(&Key::generate(), b"session-a");
+        assert_eq!(
+            p.verify(&planted, attacker.issue_req_token(&planted)),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn revoking_the_family() {
+        let store = Arc::new(MemoryRevocationStore::new());
+        let sink = Arc::new(MemoryAuditSink::new());
+        let config = XsrfConfig::new()
+            .with_revocation_store(store.clone())
+            .with_audit_sink(sink.clone());
+        let p = SignedDoubleSubmit::for_session(&Key::generate(), b"session").with_config(config);
+        let family = TokenFamily::new();
+        let ct = CookieToken::new_in_family(&family);
+        let rt = p.issue_req_token(&ct);
+        p.verify(&ct, rt.clone()).unwrap();
+        store.revoke(family);
+        assert_eq!(p.verify(&ct, rt), Err(Error::Revoked));
+        assert_eq!(
+            sink.events()[2],
+            AuditEvent::TokenRejected {
+                family: family.audit_id(),
+                reason: VerifyOutcome::Revoked,
+            }
+        );
+    }
+}
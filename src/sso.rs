@@ -0,0 +1,112 @@
+//! Tokens shared between the domains of a single sign-on deployment.
+//!
+//! Cookies are not shared between `app.example.com` and `admin.example.org`,
+//! so tokens cannot be derived from a cookie both sides see. Instead, both
+//! sides share a `Key` and know the single sign-on session identifier, and
+//! tokens are derived from `HMAC(key, session_id)`. Each token names the
+//! audience it was minted for, which the verifier checks against its own.
+use crate::sha256::hmac;
+use crate::{CookieToken, Key, RequestToken, Result, XsrfConfig};
+
+const DOMAIN: &[u8] = b"xsrf sso\0";
+
+/// Issues and verifies tokens across the domains of a single sign-on
+/// deployment.
+#[derive(Clone, Debug)]
+pub struct SsoProtection {
+    key: Key,
+    config: XsrfConfig,
+}
+
+impl SsoProtection {
+    pub fn new(key: &Key) -> SsoProtection {
+        Self {
+            key: key.clone(),
+            config: XsrfConfig::default(),
+        }
+    }
+
+    /// Issue and verify request tokens with the given config.
+    pub fn with_config(mut self, config: XsrfConfig) -> SsoProtection {
+        self.config = config;
+        self
+    }
+
+    fn bind(&self, session_id: &[u8]) -> CookieToken {
+        let len = (session_id.len() as u64).to_be_bytes();
+        CookieToken::from_bytes(hmac(self.key.as_bytes(), &[DOMAIN, &len, session_id]))
+    }
+
+    /// Issue a token for the session, to be submitted to `audience`.
+    ///
+    /// Panics if `audience` is longer than 255 bytes.
+    pub fn issue(&self, session_id: &[u8], audience: &str) -> RequestToken {
+        assert!(
+            audience.len() <= crate::claims::MAX_FIELD_LEN,
+            "xsrf audience too long"
+        );
+        let mut claims = self.config.claims();
+        claims.audience = Some(audience.to_owned());
+        self.config.issue(&self.bind(session_id), claims)
+    }
+
+    /// Verify a token for the session was minted for `audience`.
+    pub fn verify(&self, session_id: &[u8], audience: &str, token: RequestToken) -> Result<()> {
+        let bound = self.bind(session_id);
+        let outcome = self
+            .config
+            .evaluate_for(&bound, &token, None, Some(audience));
+        self.config.audit_outcome(&bound, outcome);
+        outcome.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SsoProtection;
+    use crate::{AuditEvent, Error, Key, MemoryAuditSink, VerifyOutcome, XsrfConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn verifies_across_domains() {
+        let key = Key::generate();
+        let app = SsoProtection::new(&key);
+        let admin = SsoProtection::new(&key);
+        let rt = app.issue(b"sso-session", "admin.example.org");
+        admin
+            .verify(b"sso-session", "admin.example.org", rt.clone())
+            .unwrap();
+        assert_eq!(
+            admin.verify(b"sso-session", "app.example.com", rt.clone()),
+            Err(Error::WrongScope)
+        );
+        assert_eq!(
+            admin.verify(b"other-session", "admin.example.org", rt.clone()),
+            Err(Error::TokenMismatch)
+        );
+        assert_eq!(
+            SsoProtection::new(&Key::generate()).verify(b"sso-session", "admin.example.org", rt),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn wrong_audience_is_audited() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let config = XsrfConfig::new().with_audit_sink(sink.clone());
+        let sso = SsoProtection::new(&Key::generate()).with_config(config);
+        let rt = sso.issue(b"sso-session", "admin.example.org");
+        assert_eq!(
+            sso.verify(b"sso-session", "app.example.com", rt),
+            Err(Error::WrongScope)
+        );
+        let family = sso.bind(b"sso-session").family().audit_id();
+        assert_eq!(
+            sink.events().last(),
+            Some(&AuditEvent::TokenRejected {
+                family,
+                reason: VerifyOutcome::WrongAudience,
+            })
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Encoding tokens without allocating.
+//!
+//! The encoded lengths of tokens without claims are constants, so their
+//! default encoding fits in a fixed size buffer on the stack.
+use crate::{CookieToken, RequestToken, TOKEN_LEN};
+use std::fmt;
+use std::ops::Deref;
+
+/// Length of a `CookieToken` in its default encoding.
+pub const COOKIE_TOKEN_ENCODED_LEN: usize = 44;
+/// Length of a `RequestToken` without claims in its default encoding.
+pub const REQUEST_TOKEN_ENCODED_LEN: usize = COOKIE_TOKEN_ENCODED_LEN * 2;
+
+/// An encoded token stored inline. It dereferences to `str`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StackString<const N: usize> {
+    buf: [u8; N],
+}
+
+impl<const N: usize> StackString<N> {
+    pub fn as_str(&self) -> &str {
+        // only ever filled with base64 output, which is ASCII.
+        std::str::from_utf8(&self.buf).expect("encoded token is ASCII")
+    }
+}
+
+impl<const N: usize> Deref for StackString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Display for StackString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for StackString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+fn encode_into(data: &[u8; TOKEN_LEN], out: &mut [u8]) {
+    let n = base64::encode_config_slice(data, base64::URL_SAFE, out);
+    debug_assert_eq!(n, COOKIE_TOKEN_ENCODED_LEN);
+}
+
+impl CookieToken {
+    /// Encode the token like `to_string`, without allocating.
+    pub fn to_stack_string(&self) -> StackString<COOKIE_TOKEN_ENCODED_LEN> {
+        let mut buf = [0; COOKIE_TOKEN_ENCODED_LEN];
+        encode_into(&self.data, &mut buf);
+        StackString { buf }
+    }
+
+    /// Generate a request token along with its default encoding, which is
+    /// built on the stack and copied into a single exactly sized allocation.
+    pub fn gen_req_token_string(&self) -> (RequestToken, String) {
+        let rt = self.gen_req_token();
+        let s = rt
+            .to_stack_string()
+            .expect("plain request tokens have a fixed length")
+            .as_str()
+            .to_owned();
+        (rt, s)
+    }
+}
+
+impl RequestToken {
+    /// Encode the token like `to_string`, without allocating. Returns `None`
+    /// for tokens carrying claims, which have no fixed length.
+    pub fn to_stack_string(&self) -> Option<StackString<REQUEST_TOKEN_ENCODED_LEN>> {
+        if !self.claims.is_empty() {
+            return None;
+        }
+        let mut buf = [0; REQUEST_TOKEN_ENCODED_LEN];
+        let (otp, mask) = buf.split_at_mut(COOKIE_TOKEN_ENCODED_LEN);
+        encode_into(&self.otp, otp);
+        encode_into(&self.mask, mask);
+        Some(StackString { buf })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::REQUEST_TOKEN_ENCODED_LEN;
+    use crate::CookieToken;
+
+    #[test]
+    fn stack_string_matches_to_string() {
+        let ct = CookieToken::new();
+        assert_eq!(ct.to_stack_string().as_str(), ct.to_string());
+        assert_eq!(ct.encoded(), ct.to_string());
+        assert_eq!(ct.encoded(), ct.to_string());
+        let rt = ct.gen_req_token();
+        assert_eq!(&*rt.to_stack_string().unwrap(), rt.to_string());
+        assert!(ct.gen_req_token_purpose("x").to_stack_string().is_none());
+    }
+
+    #[test]
+    fn gen_req_token_string() {
+        let ct = CookieToken::new();
+        let (rt, s) = ct.gen_req_token_string();
+        assert_eq!(s, rt.to_string());
+        assert_eq!(s.capacity(), REQUEST_TOKEN_ENCODED_LEN);
+        ct.verify_req_token(rt).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+//! Helpers for end-to-end CSRF tests of downstream apps.
+//!
+//! A [`TestClient`] drives an in-process app the way a browser would: it
+//! keeps the cookies the app sets, scrapes the request token from the pages
+//! it serves, and submits it with protected requests. The app is a closure
+//! from [`ClientRequest`] to [`ClientResponse`], which for axum or actix is a
+//! small adapter around their own test utilities.
+use crate::percent::encode;
+use crate::Request;
+
+/// A request sent by a `TestClient`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request for ClientRequest {
+    fn method(&self) -> &str {
+        &self.method
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A response received by a `TestClient`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl ClientResponse {
+    /// The value of the first header named `name`, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Drives an app through CSRF round trips.
+///
+/// By default the token is scraped from, and submitted in, the `xsrf` form
+/// field, and is also picked up from the `X-XSRF-Token` response header.
+pub struct TestClient<F> {
+    app: F,
+    field: String,
+    header: String,
+    cookies: Vec<(String, String)>,
+    token: Option<String>,
+}
+
+impl<F: FnMut(&ClientRequest) -> ClientResponse> TestClient<F> {
+    pub fn new(app: F) -> TestClient<F> {
+        Self {
+            app,
+            field: "xsrf".to_owned(),
+            header: "X-XSRF-Token".to_owned(),
+            cookies: Vec::new(),
+            token: None,
+        }
+    }
+
+    /// The name of the form field or meta tag carrying the token.
+    pub fn with_field(mut self, field: &str) -> TestClient<F> {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// The name of the header carrying the token.
+    pub fn with_header(mut self, header: &str) -> TestClient<F> {
+        self.header = header.to_owned();
+        self
+    }
+
+    /// The most recently scraped request token.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// The value of the cookie named `name`, as last set by the app.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Fetch a page, keeping its cookies and token.
+    pub fn get(&mut self, path: &str) -> ClientResponse {
+        self.send("GET", path, Vec::new(), Vec::new())
+    }
+
+    /// Submit a urlencoded form, adding the current token to `fields`.
+    pub fn post_form(&mut self, path: &str, fields: &[(&str, &str)]) -> ClientResponse {
+        let token = self.token.clone();
+        let token = token.as_ref().map(|t| (self.field.as_str(), t.as_str()));
+        let body = fields
+            .iter()
+            .copied()
+            .chain(token)
+            .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let headers = vec![(
+            "Content-Type".to_owned(),
+            "application/x-www-form-urlencoded".to_owned(),
+        )];
+        self.send("POST", path, headers, body.into_bytes())
+    }
+
+    /// Send a request with the current token in the token header.
+    pub fn post_with_header(&mut self, path: &str, body: &[u8]) -> ClientResponse {
+        let headers = self
+            .token
+            .iter()
+            .map(|t| (self.header.clone(), t.clone()))
+            .collect();
+        self.send("POST", path, headers, body.to_vec())
+    }
+
+    /// Send an arbitrary request, with the cookies but without the token.
+    pub fn send(
+        &mut self,
+        method: &str,
+        path: &str,
+        mut headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> ClientResponse {
+        if !self.cookies.is_empty() {
+            let cookie = self
+                .cookies
+                .iter()
+                .map(|(n, v)| format!("{}={}", n, v))
+                .collect::<Vec<_>>()
+                .join("; ");
+            headers.push(("Cookie".to_owned(), cookie));
+        }
+        let req = ClientRequest {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            headers,
+            body,
+        };
+        let res = (self.app)(&req);
+        self.observe(&res);
+        res
+    }
+
+    fn observe(&mut self, res: &ClientResponse) {
+        for (name, value) in res.headers.iter() {
+            if name.eq_ignore_ascii_case("set-cookie") {
+                self.set_cookie(value);
+            }
+        }
+        let token = res
+            .header(&self.header)
+            .map(str::to_owned)
+            .or_else(|| scrape_token(&res.body, &self.field));
+        if token.is_some() {
+            self.token = token;
+        }
+    }
+
+    fn set_cookie(&mut self, set_cookie: &str) {
+        let pair = set_cookie.split(';').next().unwrap_or("");
+        let (name, value) = match pair.split_once('=') {
+            Some((n, v)) => (n.trim(), v.trim()),
+            None => return,
+        };
+        self.cookies.retain(|(n, _)| n != name);
+        let expired = set_cookie
+            .split(';')
+            .any(|a| a.trim().eq_ignore_ascii_case("max-age=0"));
+        if !expired {
+            self.cookies.push((name.to_owned(), value.to_owned()));
+        }
+    }
+}
+
+/// Find the request token in an HTML page, in either an `<input>` named
+/// `field` or a `<meta>` tag named `field`.
+pub fn scrape_token(html: &str, field: &str) -> Option<String> {
+    html.split('<').skip(1).find_map(|tag| {
+        let tag = tag.split('>').next()?;
+        let (element, attrs) = tag.split_once(char::is_whitespace)?;
+        let value = match element.to_ascii_lowercase().as_str() {
+            "input" => "value",
+            "meta" => "content",
+            _ => return None,
+        };
+        let attrs = attributes(attrs);
+        let get = |name: &str| {
+            attrs
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        };
+        if get("name").as_deref() == Some(field) {
+            get(value)
+        } else {
+            None
+        }
+    })
+}
+
+/// Parse the attributes of a tag, with double, single or no quotes.
+fn attributes(mut s: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    loop {
+        s = s.trim_start();
+        let name_end = s
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(s.len());
+        if name_end == 0 {
+            return out;
+        }
+        let name = &s[..name_end];
+        s = s[name_end..].trim_start();
+        let value = match s.strip_prefix('=').map(str::trim_start) {
+            Some(rest) => {
+                let (value, rest) = match rest.chars().next() {
+                    Some(q @ '"') | Some(q @ '\'') => {
+                        let end = rest[1..].find(q).map_or(rest.len(), |i| i + 1);
+                        (&rest[1..end], rest.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                        (&rest[..end], &rest[end..])
+                    }
+                };
+                s = rest;
+                value.replace("&amp;", "&")
+            }
+            None => String::new(),
+        };
+        out.push((name.to_owned(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scrape_token, ClientRequest, ClientResponse, TestClient};
+    use crate::{CookieToken, Extractor, Request, RequestToken, TokenCookie};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn scrape_input_and_meta() {
+        let html =
+            r#"<meta name="xsrf" content="a.b="><form><input type=hidden name='xsrf' value="c=">"#;
+        assert_eq!(scrape_token(html, "xsrf").as_deref(), Some("a.b="));
+        let html = r#"<input value="c=" name="xsrf" />"#;
+        assert_eq!(scrape_token(html, "xsrf").as_deref(), Some("c="));
+        assert_eq!(scrape_token(html, "other"), None);
+    }
+
+    fn app(req: &ClientRequest) -> ClientResponse {
+        let cookie = TokenCookie::default();
+        match req.method.as_str() {
+            "GET" => {
+                let ct = CookieToken::new();
+                let form = format!(
+                    r#"<form method="post"><input type="hidden" name="xsrf" value="{}"></form>"#,
+                    ct.gen_req_token()
+                );
+                ClientResponse {
+                    status: 200,
+                    headers: vec![(
+                        "Set-Cookie".to_owned(),
+                        cookie.set_cookie_header(&ct.to_string()).unwrap(),
+                    )],
+                    body: form,
+                }
+            }
+            _ => {
+                let verified = req
+                    .header("cookie")
+                    .and_then(|h| CookieToken::from_cookie_header(h, &cookie.name).ok())
+                    .zip(Extractor::new().extract_token(req, &req.body))
+                    .and_then(|(ct, rt)| {
+                        let rt = RequestToken::try_from(rt.as_str()).ok()?;
+                        ct.verify_req_token(rt).ok()
+                    });
+                ClientResponse {
+                    status: if verified.is_some() { 200 } else { 403 },
+                    ..ClientResponse::default()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut client = TestClient::new(app);
+        assert_eq!(client.post_form("/", &[]).status, 403);
+        client.get("/");
+        assert!(client.cookie("xsrf").is_some());
+        assert_eq!(client.post_form("/", &[("name", "a b")]).status, 200);
+        assert_eq!(client.post_with_header("/", b"").status, 200);
+    }
+}
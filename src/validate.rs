@@ -0,0 +1,213 @@
+//! Checking a configuration for dangerous combinations at startup.
+use crate::{CookieError, CorsPolicy, Extractor, Key, TokenCookie, XsrfConfig, MIN_KEY_LEN};
+
+/// A dangerous combination of settings.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Cookie(#[from] CookieError),
+    #[error("xsrf cookie is readable from JavaScript, which no client needs")]
+    ReadableCookie,
+    #[error("xsrf key is shorter than {} bytes", MIN_KEY_LEN)]
+    ShortKey,
+    #[error("xsrf max uses has no effect without a replay store")]
+    MaxUsesWithoutReplayStore,
+    #[error("xsrf leeway is at least as long as the ttl")]
+    LeewayExceedsTtl,
+    #[error("xsrf max lifetime is shorter than the ttl")]
+    MaxLifetimeBelowTtl,
+    #[error("xsrf config scope does not match the cookie scope")]
+    ScopeMismatch,
+    #[error("xsrf extractor normalization is enabled in strict mode")]
+    LenientParsing,
+    #[error("cors reflects any origin with credentials, letting any site read xsrf tokens")]
+    CorsReflectedOrigin,
+    #[error("cors allows the null origin with credentials, letting any site read xsrf tokens")]
+    CorsNullOrigin,
+    #[error("cors allows an http origin with credentials, exposing xsrf tokens to the network")]
+    CorsInsecureOrigin,
+}
+
+/// The checks run by `XsrfConfig::validate`, given the rest of the setup.
+#[derive(Debug)]
+#[must_use = "call errors to run the checks"]
+pub struct Validation<'a> {
+    config: &'a XsrfConfig,
+    cookie: Option<&'a TokenCookie>,
+    key: Option<&'a Key>,
+    extractor: Option<&'a Extractor>,
+    cors: Option<&'a CorsPolicy>,
+    readable_cookie: bool,
+    strict: bool,
+}
+
+impl XsrfConfig {
+    /// Check this config, and the parts of the setup it is used with, for
+    /// dangerous combinations. Meant to be run before the server starts
+    /// taking traffic, such as
+    /// `config.validate().cookie(&cookie).key(&key).errors()`.
+    pub fn validate(&self) -> Validation<'_> {
+        Validation {
+            config: self,
+            cookie: None,
+            key: None,
+            extractor: None,
+            cors: None,
+            readable_cookie: false,
+            strict: false,
+        }
+    }
+}
+
+impl<'a> Validation<'a> {
+    /// Check the token cookie.
+    pub fn cookie(mut self, cookie: &'a TokenCookie) -> Validation<'a> {
+        self.cookie = Some(cookie);
+        self
+    }
+
+    /// Check the key used by a keyed strategy.
+    pub fn key(mut self, key: &'a Key) -> Validation<'a> {
+        self.key = Some(key);
+        self
+    }
+
+    /// Check the extractor finding request tokens.
+    pub fn extractor(mut self, extractor: &'a Extractor) -> Validation<'a> {
+        self.extractor = Some(extractor);
+        self
+    }
+
+    /// Check the app's CORS policy does not let other sites read tokens.
+    pub fn cors(mut self, cors: &'a CorsPolicy) -> Validation<'a> {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Allow a cookie without `HttpOnly`, for JavaScript clients that read
+    /// the cookie token and send it back in a header, as Angular does.
+    pub fn readable_cookie(mut self) -> Validation<'a> {
+        self.readable_cookie = true;
+        self
+    }
+
+    /// Reject anything that relaxes parsing, such as extractor
+    /// normalization.
+    pub fn strict(mut self) -> Validation<'a> {
+        self.strict = true;
+        self
+    }
+
+    /// Every problem found, empty if there are none.
+    pub fn errors(self) -> Vec<ConfigError> {
+        let mut errors = self.config.config_errors();
+        if let Some(cookie) = self.cookie {
+            errors.extend(cookie.check_prefix().err().map(ConfigError::from));
+            errors.extend(cookie.check_same_site().err().map(ConfigError::from));
+            if !cookie.http_only && !self.readable_cookie {
+                errors.push(ConfigError::ReadableCookie);
+            }
+            if !self.config.matches_scope(&cookie.scope()) {
+                errors.push(ConfigError::ScopeMismatch);
+            }
+        }
+        if self.key.is_some_and(|k| k.as_bytes().len() < MIN_KEY_LEN) {
+            errors.push(ConfigError::ShortKey);
+        }
+        if self.strict && self.extractor.is_some_and(Extractor::normalizes) {
+            errors.push(ConfigError::LenientParsing);
+        }
+        if let Some(cors) = self.cors.filter(|c| c.allows_credentials()) {
+            if cors.reflects_origin() {
+                errors.push(ConfigError::CorsReflectedOrigin);
+            }
+            if cors.allows_null_origin() {
+                errors.push(ConfigError::CorsNullOrigin);
+            }
+            if cors.allows_insecure_origin() {
+                errors.push(ConfigError::CorsInsecureOrigin);
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigError;
+    use crate::{CookieError, CorsPolicy, Extractor, Key, SameSite, TokenCookie, XsrfConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn reports_every_problem() {
+        let config = XsrfConfig::new()
+            .with_ttl(Duration::from_secs(60))
+            .with_leeway(Duration::from_secs(60))
+            .with_max_uses(3);
+        let cookie = TokenCookie {
+            secure: false,
+            http_only: false,
+            same_site: Some(SameSite::None),
+            ..TokenCookie::default()
+        };
+        let key = Key::new(b"short");
+        let extractor = Extractor::new().with_normalization();
+        let errors = config
+            .validate()
+            .cookie(&cookie)
+            .key(&key)
+            .extractor(&extractor)
+            .strict()
+            .errors();
+        assert_eq!(
+            errors,
+            [
+                ConfigError::MaxUsesWithoutReplayStore,
+                ConfigError::LeewayExceedsTtl,
+                ConfigError::Cookie(CookieError::SameSiteNoneWithoutSecure),
+                ConfigError::ReadableCookie,
+                ConfigError::ShortKey,
+                ConfigError::LenientParsing,
+            ]
+        );
+    }
+
+    #[test]
+    fn cors_cross_check() {
+        let config = XsrfConfig::new();
+        let cors = CorsPolicy::new()
+            .with_reflected_origin()
+            .with_origin("null")
+            .with_origin("http://app.example.com")
+            .with_origin("http://localhost:3000");
+        assert!(config.validate().cors(&cors).errors().is_empty());
+        let cors = cors.with_credentials();
+        assert_eq!(
+            config.validate().cors(&cors).errors(),
+            [
+                ConfigError::CorsReflectedOrigin,
+                ConfigError::CorsNullOrigin,
+                ConfigError::CorsInsecureOrigin,
+            ]
+        );
+        let cors = CorsPolicy::new()
+            .with_origin("https://app.example.com")
+            .with_credentials();
+        assert!(config.validate().cors(&cors).errors().is_empty());
+        for origin in ["http://[::1]:3000", "http://[::1]", "http://127.0.0.1:8080"] {
+            let cors = CorsPolicy::new().with_origin(origin).with_credentials();
+            assert!(
+                config.validate().cors(&cors).errors().is_empty(),
+                "{}",
+                origin
+            );
+        }
+        let cors = CorsPolicy::new()
+            .with_origin("http://[2001:db8::1]:3000")
+            .with_credentials();
+        assert_eq!(
+            config.validate().cors(&cors).errors(),
+            [ConfigError::CorsInsecureOrigin]
+        );
+    }
+}
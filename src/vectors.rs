@@ -0,0 +1,196 @@
+//! Known answer test vectors for ports of the token scheme.
+//!
+//! Clients in other languages issuing or verifying tokens can check
+//! themselves against these. The vectors are computed by this crate, so they
+//! always describe the current format, and `test_vectors_json` exports them.
+use crate::claims::Claims;
+use crate::json::Object;
+use crate::{CookieToken, RequestToken, TOKEN_LEN};
+use std::convert::TryFrom;
+
+/// A single test vector.
+///
+/// `request_token` is issued from `issuer_cookie_token` using `otp` and the
+/// given claims, then verified against `cookie_token`. Tokens are in their
+/// default encoding, and `otp` is URL safe base64.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TestVector {
+    pub name: &'static str,
+    pub issuer_cookie_token: String,
+    pub otp: String,
+    pub issued_at: Option<u64>,
+    pub purpose: Option<&'static str>,
+    pub request_token: String,
+    pub cookie_token: String,
+    /// The `Error::code` verification fails with, or `None` if it succeeds.
+    pub expected_error: Option<&'static str>,
+}
+
+struct Spec {
+    name: &'static str,
+    issuer: u8,
+    otp: u8,
+    issued_at: Option<u64>,
+    purpose: Option<&'static str>,
+    verifier: u8,
+    tamper: fn(String) -> String,
+    expected_error: Option<&'static str>,
+}
+
+const SPECS: &[Spec] = &[
+    Spec {
+        name: "plain",
+        issuer: 7,
+        otp: 1,
+        issued_at: None,
+        purpose: None,
+        verifier: 7,
+        tamper: |s| s,
+        expected_error: None,
+    },
+    Spec {
+        name: "issued at",
+        issuer: 7,
+        otp: 2,
+        issued_at: Some(1_600_000_000),
+        purpose: None,
+        verifier: 7,
+        tamper: |s| s,
+        expected_error: None,
+    },
+    Spec {
+        name: "issued at and purpose",
+        issuer: 7,
+        otp: 3,
+        issued_at: Some(1_600_000_000),
+        purpose: Some("password-change"),
+        verifier: 7,
+        tamper: |s| s,
+        expected_error: None,
+    },
+    Spec {
+        name: "wrong cookie token",
+        issuer: 7,
+        otp: 1,
+        issued_at: None,
+        purpose: None,
+        verifier: 8,
+        tamper: |s| s,
+        expected_error: Some("xsrf.mismatch"),
+    },
+    Spec {
+        name: "tampered claims",
+        issuer: 7,
+        otp: 2,
+        issued_at: Some(1_600_000_000),
+        purpose: None,
+        verifier: 7,
+        tamper: |s| s.replace("F9eEAA", "F9eEAE"),
+        expected_error: Some("xsrf.mismatch"),
+    },
+    Spec {
+        name: "truncated",
+        issuer: 7,
+        otp: 1,
+        issued_at: None,
+        purpose: None,
+        verifier: 7,
+        tamper: |s| s[..s.len() - 4].to_owned(),
+        expected_error: Some("xsrf.malformed"),
+    },
+];
+
+/// The canonical test vectors.
+pub fn test_vectors() -> Vec<TestVector> {
+    SPECS
+        .iter()
+        .map(|spec| {
+            let issuer = CookieToken::from_bytes([spec.issuer; TOKEN_LEN]);
+            let claims = Claims {
+                issued_at: spec.issued_at,
+                origin: None,
+                purpose: spec.purpose.map(str::to_owned),
+                scope: None,
+                audience: None,
+                max_uses: None,
+                environment: None,
+            };
+            let otp = [spec.otp; TOKEN_LEN];
+            let request_token = issuer.issue_with_otp(otp, claims).to_string();
+            TestVector {
+                name: spec.name,
+                issuer_cookie_token: issuer.to_string(),
+                otp: base64::encode_config(otp, base64::URL_SAFE),
+                issued_at: spec.issued_at,
+                purpose: spec.purpose,
+                request_token: (spec.tamper)(request_token),
+                cookie_token: CookieToken::from_bytes([spec.verifier; TOKEN_LEN]).to_string(),
+                expected_error: spec.expected_error,
+            }
+        })
+        .collect()
+}
+
+/// The canonical test vectors as a JSON array of objects, with the same
+/// member names as the `TestVector` fields. Absent values are `null`.
+pub fn test_vectors_json() -> String {
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_owned());
+    let quoted = |v: &str| {
+        let mut s = String::new();
+        crate::json::push_str(&mut s, v);
+        s
+    };
+    let items: Vec<String> = test_vectors()
+        .iter()
+        .map(|v| {
+            Object::new()
+                .str("name", v.name)
+                .str("issuer_cookie_token", &v.issuer_cookie_token)
+                .str("otp", &v.otp)
+                .raw("issued_at", &opt(v.issued_at.map(|t| t.to_string())))
+                .raw("purpose", &opt(v.purpose.map(quoted)))
+                .str("request_token", &v.request_token)
+                .str("cookie_token", &v.cookie_token)
+                .raw("expected_error", &opt(v.expected_error.map(quoted)))
+                .finish()
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+impl TestVector {
+    /// Verify the vector with this crate, returning the error code if any.
+    pub fn verify(&self) -> Option<&'static str> {
+        let result = CookieToken::try_from(self.cookie_token.as_str()).and_then(|ct| {
+            ct.verify_req_token(RequestToken::try_from(self.request_token.as_str())?)
+        });
+        result.err().map(|err| err.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_vectors, test_vectors_json};
+
+    #[test]
+    fn vectors_verify_as_expected() {
+        let vectors = test_vectors();
+        // pinned independently of this implementation.
+        assert_eq!(
+            vectors[0].request_token,
+            "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=BgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgY="
+        );
+        assert_eq!(
+            vectors[1].request_token,
+            "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=5EhYaVtHQdBuucXuwMwVdwErW-fKxhPPbciOiy8VwcM=.AgEIAAAAAF9eEAA="
+        );
+        vectors
+            .iter()
+            .for_each(|v| assert_eq!(v.verify(), v.expected_error, "{}", v.name));
+        let json = test_vectors_json();
+        assert!(json.starts_with("[{\"name\":\"plain\","));
+        assert!(json.contains("\"expected_error\":null"));
+        assert!(json.contains("\"expected_error\":\"xsrf.malformed\"}]"));
+    }
+}
@@ -0,0 +1,31 @@
+//! Proof that a token was verified.
+//!
+//! [`Verified`] can only be constructed by verification, so downstream code
+//! performing state changing actions can take a `&Verified<RequestToken>`
+//! argument and have the type system prove verification happened first. The
+//! crate's own helpers for verified requests, such as `refresh_header`, do.
+use std::ops::Deref;
+
+/// A value that passed verification.
+#[derive(Debug)]
+pub struct Verified<T> {
+    inner: T,
+}
+
+impl<T> Verified<T> {
+    pub(crate) fn new(inner: T) -> Verified<T> {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Deref for Verified<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
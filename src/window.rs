@@ -0,0 +1,108 @@
+//! Request tokens that rotate with time, like TOTP codes.
+//!
+//! Instead of embedding a timestamp, the value a windowed token masks is
+//! `HMAC(cookie_token, step)`, where `step` counts fixed length windows since
+//! the unix epoch. Tokens are accepted during the window they were issued in
+//! and the one after, so they expire without claims or server side state.
+//! An expired token is indistinguishable from a forged one, and fails with
+//! `Error::TokenMismatch`.
+//!
+//! Pads come from the config's `Entropy`, and issuing and verifying record
+//! audit events like any other token. The rest of the config applies too,
+//! such as revocation and replay stores, but windowed tokens carry no
+//! claims, so the config should not require a TTL.
+use crate::claims::Claims;
+use crate::sha256::hmac;
+use crate::{CookieToken, RequestToken, Result, VerifyOutcome, XsrfConfig};
+use std::time::Duration;
+
+const DOMAIN: &[u8] = b"xsrf window\0";
+
+impl CookieToken {
+    /// The token windowed request tokens of `step` are derived from.
+    fn windowed(&self, step: u64) -> CookieToken {
+        self.derive_child(hmac(&self.data, &[DOMAIN, &step.to_be_bytes()]))
+    }
+
+    /// Generate a request token valid for the current time window of length
+    /// `window`, and the following one. Time is read from `config`.
+    ///
+    /// Panics if `window` is shorter than a second.
+    pub fn gen_req_token_windowed(&self, window: Duration, config: &XsrfConfig) -> RequestToken {
+        let windowed = self.windowed(step(window, config));
+        config.issue_with_otp(&windowed, config.otp(), Claims::default())
+    }
+
+    /// Verify a token issued by `gen_req_token_windowed` with the same
+    /// `window`, during the window it was issued in or the following one.
+    pub fn verify_req_token_windowed(
+        &self,
+        token: RequestToken,
+        window: Duration,
+        config: &XsrfConfig,
+    ) -> Result<()> {
+        let step = step(window, config);
+        // check both windows regardless, to not reveal which one matched.
+        let current = config.evaluate(&self.windowed(step), &token, None);
+        let previous = config.evaluate(&self.windowed(step.wrapping_sub(1)), &token, None);
+        let outcome = match (current, previous) {
+            _ if !token.claims.is_empty() => VerifyOutcome::Malformed,
+            (VerifyOutcome::Mismatch, previous) => previous,
+            (current, _) => current,
+        };
+        config.audit_outcome(self, outcome);
+        outcome.into_result()
+    }
+}
+
+fn step(window: Duration, config: &XsrfConfig) -> u64 {
+    let window = window.as_secs();
+    assert!(window > 0, "xsrf window must be at least a second");
+    config.now() / window
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AuditEvent, CookieToken, Error, MemoryAuditSink, TestClock, XsrfConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn windowed_tokens_expire() {
+        let clock = TestClock::new(600);
+        let config = XsrfConfig::new().with_clock(clock.clone());
+        let window = Duration::from_secs(300);
+        let ct = CookieToken::new();
+        let rt = ct.gen_req_token_windowed(window, &config);
+        assert_eq!(ct.verify_req_token(rt.clone()), Err(Error::TokenMismatch));
+        clock.set(899);
+        ct.verify_req_token_windowed(rt.clone(), window, &config)
+            .unwrap();
+        clock.set(1199);
+        ct.verify_req_token_windowed(rt.clone(), window, &config)
+            .unwrap();
+        clock.set(1200);
+        assert_eq!(
+            ct.verify_req_token_windowed(rt, window, &config),
+            Err(Error::TokenMismatch)
+        );
+    }
+
+    #[test]
+    fn windowed_tokens_are_audited() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let config = XsrfConfig::new().with_audit_sink(sink.clone());
+        let window = Duration::from_secs(300);
+        let ct = CookieToken::new();
+        let family = ct.family().audit_id();
+        let rt = ct.gen_req_token_windowed(window, &config);
+        ct.verify_req_token_windowed(rt, window, &config).unwrap();
+        assert_eq!(
+            sink.events(),
+            [
+                AuditEvent::TokenIssued { family },
+                AuditEvent::TokenVerified { family },
+            ]
+        );
+    }
+}